@@ -0,0 +1,300 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::hash::Hash;
+
+/// The suit of a card. In the standard rules, cards of the same rank are equal whatever their suits.
+/// Suits are ordered as in bridge, from clubs (lowest) to spades.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Suit {
+  Clubs,
+  Diamonds,
+  Hearts,
+  Spades,
+}
+
+impl Suit {
+  pub const ALL: [Suit; 4] = [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades];
+}
+
+/// The rank of a joker, above every other rank so that it beats every other card.
+pub const JOKER: u8 = u8::MAX;
+
+/// A playing card: a rank and a suit, or a joker. Ranks count from 1, the lowest, so that in a
+/// standard deck 1 is a two and 13 is an ace. A joker has the rank `JOKER`.
+/// Cards are ordered by rank, then suit. Displayed and serialized as e.g. `10H`, `AS`, or `JK`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Card {
+  pub rank: u8,
+  /// `None` for a joker
+  pub suit: Option<Suit>,
+}
+
+impl fmt::Display for Card {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    const NAMES: [&str; 13] = [
+      "2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K", "A",
+    ];
+
+    let Some(suit) = self.suit else {
+      return write!(f, "JK");
+    };
+
+    match NAMES.get((self.rank as usize).wrapping_sub(1)) {
+      Some(name) => write!(f, "{name}"),
+      None => write!(f, "{}", self.rank),
+    }?;
+
+    let suit = match suit {
+      Suit::Clubs => 'C',
+      Suit::Diamonds => 'D',
+      Suit::Hearts => 'H',
+      Suit::Spades => 'S',
+    };
+    write!(f, "{suit}")
+  }
+}
+
+impl Serialize for Card {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
+}
+
+/// House rules under which suits decide battles, as well as ranks.
+#[derive(Clone, Copy, Default)]
+pub struct SuitRules {
+  /// Every card of this suit beats every card of the other suits (but not a joker)
+  pub trump: Option<Suit>,
+  /// Cards of equal rank are ranked by suit, instead of starting a war. Only identical cards (from
+  /// different decks of a shoe) start a war
+  pub tie_break: bool,
+}
+
+impl SuitRules {
+  /// Whether any rule looks at suits, so that the game must be played with `Card`s.
+  pub fn any(&self) -> bool {
+    self.trump.is_some() || self.tie_break
+  }
+}
+
+/// A card as the game sees it. Rules that only compare ranks are played with bare `u8` ranks, which
+/// are cheaper to shuffle and move than full `Card`s; rules that look at suits are played with `Card`s.
+pub trait Ranked: Copy + Eq + Hash + Serialize {
+  fn from_card(card: Card) -> Self;
+  fn rank(self) -> u8;
+  /// The strength of the card in a battle: the higher card wins, and equal cards start a war.
+  /// Never zero, so that a card can be made to lose to every other.
+  fn key(self, rules: SuitRules) -> u32;
+
+  fn is_joker(self) -> bool {
+    self.rank() == JOKER
+  }
+}
+
+impl Ranked for u8 {
+  #[inline(always)]
+  fn from_card(card: Card) -> Self {
+    card.rank
+  }
+
+  #[inline(always)]
+  fn rank(self) -> u8 {
+    self
+  }
+
+  /// Bare ranks have no suits, so the suit rules are ignored.
+  #[inline(always)]
+  fn key(self, _: SuitRules) -> u32 {
+    self as u32 + 1
+  }
+}
+
+impl Ranked for Card {
+  fn from_card(card: Card) -> Self {
+    card
+  }
+
+  fn rank(self) -> u8 {
+    self.rank
+  }
+
+  /// Trumps (and jokers, if there is a trump suit) come first, then ranks, then suits if they break
+  /// ties.
+  fn key(self, rules: SuitRules) -> u32 {
+    let trump = match self.suit {
+      Some(suit) => rules.trump == Some(suit),
+      None => rules.trump.is_some(),
+    };
+    let suit = match self.suit {
+      Some(suit) if rules.tie_break => suit as u32 + 1,
+      _ => 0,
+    };
+
+    // The rank field takes 9 bits, since a joker's rank is `JOKER`, so the trump flag is above them
+    (trump as u32) << 24 | (self.rank as u32 + 1) << 8 | suit
+  }
+}
+
+/// A shoe of `decks` decks shuffled together, each with `copies` copies of each of `ranks` ranks and
+/// `jokers` jokers. The copies of a rank are given the suits in turn, so that `shoe(1, 13, 4, 0)` is
+/// a standard 52-card deck, `shoe(1, 13, 4, 2)` the 54-card deck with jokers, and `shoe(6, 13, 4, 0)`
+/// a casino six-deck shoe.
+/// The cards are sorted by rank.
+pub fn shoe(decks: usize, ranks: u8, copies: usize, jokers: usize) -> Vec<Card> {
+  let suited = (1..=ranks).flat_map(|rank| {
+    (0..decks * copies).map(move |i| Card {
+      rank,
+      suit: Some(Suit::ALL[i % Suit::ALL.len()]),
+    })
+  });
+  let jokers = (0..decks * jokers).map(|_| Card {
+    rank: JOKER,
+    suit: None,
+  });
+
+  suited.chain(jokers).collect()
+}
+
+/// Suited cards of the given ranks, where the copies of each rank are given the suits in turn.
+pub fn from_ranks(ranks: &[u8]) -> Vec<Card> {
+  let mut copies = std::collections::HashMap::new();
+
+  ranks
+    .iter()
+    .map(|&rank| {
+      let i = copies.entry(rank).or_insert(0);
+      let suit = Suit::ALL[*i % Suit::ALL.len()];
+      *i += 1;
+      Card {
+        rank,
+        suit: Some(suit),
+      }
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const JOKER_CARD: Card = Card {
+    rank: JOKER,
+    suit: None,
+  };
+
+  /// The keys of a joker, a trump two, and a plain ace, under the given suit rules.
+  fn keys(rules: SuitRules) -> [u32; 3] {
+    let two = Card {
+      rank: 1,
+      suit: Some(Suit::Spades),
+    };
+    let ace = Card {
+      rank: 13,
+      suit: Some(Suit::Hearts),
+    };
+    [JOKER_CARD.key(rules), two.key(rules), ace.key(rules)]
+  }
+
+  #[test]
+  fn joker_beats_trump_beats_ace() {
+    for tie_break in [false, true] {
+      let rules = SuitRules {
+        trump: Some(Suit::Spades),
+        tie_break,
+      };
+      let [joker, trump, ace] = keys(rules);
+      assert!(joker > trump, "tie_break: {tie_break}");
+      assert!(trump > ace, "tie_break: {tie_break}");
+    }
+  }
+
+  #[test]
+  fn joker_beats_ace_without_trump() {
+    for tie_break in [false, true] {
+      let rules = SuitRules {
+        trump: None,
+        tie_break,
+      };
+      let [joker, two, ace] = keys(rules);
+      assert!(joker > ace && ace > two, "tie_break: {tie_break}");
+    }
+  }
+
+  fn card(rank: u8, suit: Suit) -> Card {
+    Card {
+      rank,
+      suit: Some(suit),
+    }
+  }
+
+  #[test]
+  fn tie_break_ranks_equal_ranks_by_suit() {
+    let rules = SuitRules {
+      trump: None,
+      tie_break: true,
+    };
+    let key = |card: Card| card.key(rules);
+    assert!(key(card(5, Suit::Spades)) > key(card(5, Suit::Clubs)));
+    assert!(key(card(5, Suit::Spades)) < key(card(6, Suit::Clubs)));
+
+    // Otherwise, suits are ignored
+    let key = |card: Card| card.key(SuitRules::default());
+    assert_eq!(key(card(5, Suit::Spades)), key(card(5, Suit::Clubs)));
+  }
+
+  #[test]
+  fn trump_beats_every_other_suit() {
+    let rules = SuitRules {
+      trump: Some(Suit::Clubs),
+      tie_break: false,
+    };
+    let key = |card: Card| card.key(rules);
+    assert!(key(card(1, Suit::Clubs)) > key(card(13, Suit::Spades)));
+    assert!(key(card(2, Suit::Clubs)) > key(card(1, Suit::Clubs)));
+    assert_eq!(key(card(5, Suit::Hearts)), key(card(5, Suit::Spades)));
+  }
+
+  #[test]
+  fn cards_display_by_rank_and_suit() {
+    assert_eq!(card(9, Suit::Hearts).to_string(), "10H");
+    assert_eq!(card(13, Suit::Spades).to_string(), "AS");
+    assert_eq!(card(1, Suit::Clubs).to_string(), "2C");
+    assert_eq!(JOKER_CARD.to_string(), "JK");
+    // Ranks beyond those of a standard deck are shown as numbers
+    assert_eq!(card(14, Suit::Diamonds).to_string(), "14D");
+    assert_eq!(card(0, Suit::Diamonds).to_string(), "0D");
+  }
+
+  #[test]
+  fn shoes_hold_every_copy_of_every_rank() {
+    for (decks, jokers, len) in [(1, 0, 52), (1, 2, 54), (6, 0, 312), (6, 2, 324)] {
+      let shoe = shoe(decks, 13, 4, jokers);
+      assert_eq!(shoe.len(), len, "{decks} decks, {jokers} jokers");
+      let count = |rank| shoe.iter().filter(|card| card.rank == rank).count();
+      assert_eq!(count(13), 4 * decks);
+      assert_eq!(count(JOKER), jokers * decks);
+      assert!(shoe
+        .iter()
+        .all(|card| card.suit.is_none() == (card.rank == JOKER)));
+    }
+
+    // The copies of a rank are given the suits in turn, and the jokers come last
+    let names: Vec<_> = shoe(1, 2, 5, 1).iter().map(Card::to_string).collect();
+    assert_eq!(
+      names,
+      ["2C", "2D", "2H", "2S", "2C", "3C", "3D", "3H", "3S", "3C", "JK"]
+    );
+  }
+
+  #[test]
+  fn ranks_are_given_the_suits_in_turn() {
+    // Each rank takes the suits in turn on its own, whatever the order of the ranks
+    let names: Vec<_> = from_ranks(&[5, 3, 5, 5, 3, 5, 5])
+      .iter()
+      .map(Card::to_string)
+      .collect();
+    assert_eq!(names, ["6C", "4C", "6D", "6H", "4D", "6S", "6C"]);
+  }
+}
@@ -0,0 +1,527 @@
+use crate::card::Suit;
+use crate::sim::{Comparison, HonorRule, LootOrder, OutOfCards, Refill, WarSize};
+use crate::strategy::{LootStrategy, PlayStrategy};
+use clap::builder::RangedU64ValueParser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Simulates the War card game and some variants.
+/// Without a subcommand, runs the standard suite of games and the small-games table.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+  #[command(subcommand)]
+  pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+  /// Simulates many games of a single setup, and prints summary statistics
+  Simulate {
+    #[command(flatten)]
+    deck: DeckArgs,
+    #[command(flatten)]
+    params: ParamsArgs,
+    #[command(flatten)]
+    run: RunArgs,
+    /// Writes a record of every game (seed, result, turns, wars, honor removals, and reshuffles) to this
+    /// file, as CSV if it ends in `.csv` and as JSON Lines otherwise
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+  },
+
+  /// Simulates small-deck games of n unique cards per player with k face-down cards in a war,
+  /// and prints the mean game length of each in a table
+  Table {
+    /// Smallest number of unique cards per player
+    #[arg(long, default_value_t = 1, value_parser = RangedU64ValueParser::<u8>::new().range(1..=255))]
+    n_min: u8,
+    /// Largest number of unique cards per player
+    #[arg(long, default_value_t = 13)]
+    n_max: u8,
+    /// Smallest number of face-down cards in a war
+    #[arg(long, default_value_t = 0)]
+    k_min: usize,
+    /// Largest number of face-down cards in a war
+    #[arg(long, default_value_t = 9)]
+    k_max: usize,
+    /// How the number of face-down cards in a war is decided; with `rank`, the table has a single
+    /// column
+    #[arg(long, value_enum, default_value_t)]
+    war_size: WarSize,
+    /// If a card loses a battle by this much or less, it is removed from the game
+    #[arg(long, default_value_t = 0)]
+    honor_threshold: u8,
+    /// Number of games simulated per cell
+    #[arg(short = 'n', long, default_value_t = 100_000, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    games: usize,
+    /// Computes cells with at most this many unique cards per player exactly, instead of simulating them
+    #[arg(long, default_value_t = 0)]
+    exact_max_n: u8,
+    /// Master seed, from which the seed of each game is derived; random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Number of worker threads; defaults to the number of available cores
+    #[arg(long)]
+    threads: Option<usize>,
+  },
+
+  /// Computes the exact win probabilities and expected length of a small game with shuffled discards,
+  /// by solving its Markov chain
+  Exact {
+    #[command(flatten)]
+    deck: DeckArgs,
+    /// Number of cards flipped face-down in a war
+    #[arg(short, default_value_t = 3)]
+    k: usize,
+    /// How the number of face-down cards in a war is decided
+    #[arg(long, value_enum, default_value_t)]
+    war_size: WarSize,
+    /// If a card loses a battle by this much or less, it is removed from the game
+    #[arg(long, default_value_t = 0)]
+    honor_threshold: u8,
+    /// Gives up if the game has more than this many states
+    #[arg(long, default_value_t = 2_000_000)]
+    max_states: usize,
+    /// Estimates the game by simulating this many games as well, for comparison
+    #[arg(short = 'n', long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    games: Option<usize>,
+    /// Master seed for the simulated games
+    #[arg(long)]
+    seed: Option<u64>,
+  },
+
+  /// Simulates a single setup under several rule parameters, and prints the results side-by-side
+  Compare {
+    #[command(flatten)]
+    deck: DeckArgs,
+    /// Numbers of face-down cards in a war to compare
+    #[arg(short, value_delimiter = ',', default_value = "3")]
+    k: Vec<usize>,
+    /// Honor thresholds to compare
+    #[arg(long, value_delimiter = ',', default_value = "0,1")]
+    honor_threshold: Vec<u8>,
+    /// Honor rules to compare, under every nonzero honor threshold
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "loser")]
+    honor_rule: Vec<HonorRule>,
+    #[command(flatten)]
+    run: RunArgs,
+  },
+
+  /// Plays every pair of loot strategies against each other, and prints player 1's win rate for
+  /// each pair. Discards are never shuffled, since otherwise the order of won cards does not matter,
+  /// and games are stopped if they cycle (or after 20,000 turns, unless `--max-turns` is given)
+  HeadToHead {
+    /// Loot strategies to play against each other
+    #[arg(value_enum, default_values = ["rules", "high-first", "low-first", "interleave", "random"])]
+    strategies: Vec<LootStrategy>,
+    #[command(flatten)]
+    deck: DeckArgs,
+    #[command(flatten)]
+    params: ParamsArgs,
+    #[command(flatten)]
+    run: RunArgs,
+  },
+
+  /// Sweeps a handicap of one player, and prints player 1's win rate under each value of it, to find
+  /// the handicap that makes the game most even, e.g. of the aces against the rest of the deck
+  /// (`--player1 13x4 --player2 1x4,2x4,...`). Every other player keeps the rules given
+  Handicap {
+    /// The handicapped player, from 1
+    #[arg(long, default_value_t = 1, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    player: usize,
+    /// The rule of that player that is varied
+    #[arg(long, value_enum, default_value_t)]
+    rule: HandicapRule,
+    /// The smallest value of the rule tried
+    #[arg(long, default_value_t = 0)]
+    min: usize,
+    /// The largest value of the rule tried, at most 255 for the honor threshold
+    #[arg(long, default_value_t = 10)]
+    max: usize,
+    #[command(flatten)]
+    deck: DeckArgs,
+    #[command(flatten)]
+    params: ParamsArgs,
+    #[command(flatten)]
+    run: RunArgs,
+  },
+
+  /// Searches for the split of a deck between two players that best meets a goal, by hill climbing
+  /// over which cards each player holds, estimating every split tried by simulating games with the
+  /// same master seed. Games are stopped after 20,000 turns, unless `--max-turns` is given
+  Search {
+    /// What the search looks for
+    #[arg(long, value_enum, default_value_t)]
+    goal: SearchGoal,
+    /// Number of hill-climbing steps of each search, each of which tries swapping a card of player 1
+    /// for a card of player 2
+    #[arg(long, default_value_t = 200)]
+    steps: usize,
+    /// Number of games simulated to estimate each split tried. The best split found is then estimated
+    /// again by the run options
+    #[arg(long, default_value_t = 2_000, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    step_games: usize,
+    #[command(flatten)]
+    deck: DeckArgs,
+    #[command(flatten)]
+    params: ParamsArgs,
+    #[command(flatten)]
+    run: RunArgs,
+  },
+
+  /// Simulates games, and reports how features of player 1's initial cards (the sum of their ranks,
+  /// and their numbers of aces and high cards) predict the winner and the length of a game: the win
+  /// rate and mean length of the games binned by each feature, and linear fits of both
+  Features {
+    #[command(flatten)]
+    deck: DeckArgs,
+    #[command(flatten)]
+    params: ParamsArgs,
+    /// Width of the bins of the rank sum
+    #[arg(long, default_value_t = 10, value_parser = RangedU64ValueParser::<i64>::new().range(1..))]
+    rank_sum_bin: i64,
+    /// Number of games simulated
+    #[arg(short = 'n', long, default_value_t = 100_000, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    games: usize,
+    /// Master seed, from which the seed of each game is derived; random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Number of worker threads; defaults to the number of available cores
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Writes the binned win rates and lengths to this CSV file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+  },
+
+  /// Runs every scenario of one or more TOML or JSON scenario files (see `scenarios/standard.toml`)
+  Run {
+    /// Scenario files to run, in order
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// Master seed for every scenario, overriding the seeds in the files
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Number of worker threads; defaults to the number of available cores
+    #[arg(long)]
+    threads: Option<usize>,
+  },
+
+  /// Plots the game length histograms of files written by `simulate --output` to an SVG image
+  Plot {
+    /// Files of game lengths
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// Label of each file in the legend, in order; defaults to the file name
+    #[arg(long)]
+    label: Vec<String>,
+    /// The SVG file to write
+    #[arg(short, long, default_value = "game-lengths.svg")]
+    output: PathBuf,
+    #[command(flatten)]
+    plot: PlotArgs,
+  },
+
+  /// Replays a single game from the seed reported by `simulate`
+  Replay {
+    #[command(flatten)]
+    deck: DeckArgs,
+    #[command(flatten)]
+    params: ParamsArgs,
+    /// Seed of the game (not the master seed of the run)
+    #[arg(long)]
+    seed: u64,
+    /// Writes every event of the game to this file as JSON Lines (`-` for stdout)
+    #[arg(long)]
+    trace: Option<PathBuf>,
+  },
+}
+
+/// How the deck is built and dealt to the players.
+#[derive(Args)]
+pub struct DeckArgs {
+  /// Number of distinct card ranks in the deck
+  #[arg(long, default_value_t = 13)]
+  pub ranks: u8,
+  /// Number of copies of each rank in a deck, which are given the suits in turn
+  #[arg(long, default_value_t = 4)]
+  pub copies: usize,
+  /// Number of jokers in a deck, which beat every other card
+  #[arg(long, default_value_t = 0)]
+  pub jokers: usize,
+  /// Number of decks shuffled together into a shoe
+  #[arg(long, default_value_t = 1)]
+  pub decks: usize,
+  /// How the deck is dealt to the players
+  #[arg(long, value_enum, default_value_t = DealMode::Shuffled)]
+  pub deal: DealMode,
+  /// Number of players
+  #[arg(
+    long,
+    default_value_t = 2,
+    value_parser = RangedU64ValueParser::<usize>::new().range(2..),
+    conflicts_with = "player1"
+  )]
+  pub players: usize,
+  /// Explicit initial cards for player 1, e.g. `13x4` or `1,2,3`; overrides the deck options
+  #[arg(long, value_parser = parse_cards, requires = "player2")]
+  pub player1: Option<Cards>,
+  /// Explicit initial cards for player 2, e.g. `1x4,2x4`
+  #[arg(long, value_parser = parse_cards, requires = "player1")]
+  pub player2: Option<Cards>,
+}
+
+#[derive(Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DealMode {
+  /// The deck is shuffled, and split evenly between the players
+  Shuffled,
+  /// Each player receives an equal share of the copies of every rank, which must divide evenly
+  Split,
+}
+
+/// A rule of a single player that `Command::Handicap` varies.
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum HandicapRule {
+  /// The number of cards the player flips face-down in a war
+  #[default]
+  K,
+  /// The player's honor threshold, under which their own losing cards are removed
+  HonorThreshold,
+}
+
+/// What `Command::Search` looks for.
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum SearchGoal {
+  /// The smallest hand for player 1 that wins at least half of the games
+  #[default]
+  SmallestWinner,
+  /// The split of the deck in half that makes games longest
+  Longest,
+}
+
+/// The rules of the game. Scenario files take the same options, under the same names.
+#[derive(Args, Deserialize)]
+pub struct ParamsArgs {
+  /// Number of cards flipped face-down in a war, or in the first war of a round with an escalating
+  /// war size
+  #[arg(short, default_value_t = 3)]
+  #[serde(default = "default_k")]
+  pub k: usize,
+  /// How the number of face-down cards in a war is decided
+  #[arg(long, value_enum, default_value_t)]
+  #[serde(default)]
+  pub war_size: WarSize,
+  /// If a card loses a battle by this much or less, it is removed from the game (or other cards, see
+  /// `--honor-rule`)
+  #[arg(long, default_value_t = 0)]
+  #[serde(default)]
+  pub honor_threshold: u8,
+  /// Which cards are removed when a card loses by the honor threshold or less
+  #[arg(long, value_enum, default_value_t)]
+  #[serde(default)]
+  pub honor_rule: HonorRule,
+  /// Overrides `-k` for each player, in seat order, e.g. `1,3`. The last value is also used by the
+  /// remaining players
+  #[arg(long, value_delimiter = ',')]
+  #[serde(default)]
+  pub player_k: Vec<usize>,
+  /// Overrides `--honor-threshold` for each player, in seat order, e.g. `0,1` for only player 2 to
+  /// be subject to the honor rule. The last value is also used by the remaining players
+  #[arg(long, value_delimiter = ',')]
+  #[serde(default)]
+  pub player_honor_threshold: Vec<u8>,
+  /// Stops games that have not finished after this many turns
+  #[arg(long)]
+  pub max_turns: Option<u64>,
+  /// Stops games that return to an earlier state, and so would never finish
+  #[arg(long)]
+  #[serde(default)]
+  pub detect_cycles: bool,
+  /// What happens to a player's discard when their deck runs out
+  #[arg(long, value_enum, default_value_t)]
+  #[serde(default)]
+  pub refill: Refill,
+  /// What a player who owns too few cards to play a war in full does
+  #[arg(long, value_enum, default_value_t)]
+  #[serde(default)]
+  pub out_of_cards: OutOfCards,
+  /// Each player holds a hand of this many cards, and chooses which to play in each battle (by
+  /// `--strategy`), instead of playing the top card of their deck
+  #[arg(long, default_value_t = 0)]
+  #[serde(default)]
+  pub hand: usize,
+  /// How each player chooses which card of their hand to play, in seat order. The last strategy is
+  /// also used by the remaining players
+  #[arg(long, value_enum, value_delimiter = ',', default_value = "random")]
+  #[serde(default)]
+  pub strategy: Vec<PlayStrategy>,
+  /// The order in which the cards won in a round are added to the winner's discard
+  #[arg(long, value_enum, default_value_t)]
+  #[serde(default)]
+  pub loot_order: LootOrder,
+  /// How each player arranges the cards they win, in seat order, overriding `--loot-order`. The
+  /// last strategy is also used by the remaining players. Only matters with `--refill keep`
+  #[arg(long, value_enum, value_delimiter = ',', default_value = "rules")]
+  #[serde(default)]
+  pub loot_strategy: Vec<LootStrategy>,
+  /// How the cards flipped in a battle are compared
+  #[arg(long, value_enum, default_value_t)]
+  #[serde(default)]
+  pub comparison: Comparison,
+  /// Every card of this suit beats every card of the other suits, whatever their ranks
+  #[arg(long, value_enum)]
+  pub trump: Option<Suit>,
+  /// Cards of equal rank are ranked by suit (clubs, diamonds, hearts, then spades) instead of
+  /// starting a war, and lose by a margin of zero under the honor rule
+  #[arg(long)]
+  #[serde(default)]
+  pub suit_tie_break: bool,
+}
+
+fn default_k() -> usize {
+  3
+}
+
+/// How many games are simulated, and with what randomness.
+#[derive(Args)]
+pub struct RunArgs {
+  /// Simulates exactly this many games
+  #[arg(short = 'n', long, conflicts_with = "time", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+  pub games: Option<usize>,
+  /// Simulates games until at least this many seconds have elapsed [default: 1]. With a precision
+  /// target, stops after this many seconds even if the target is not met
+  #[arg(short, long, value_parser = parse_seconds)]
+  pub time: Option<f64>,
+  /// Simulates games until the 95% confidence interval of player 1's win rate is within this many
+  /// percentage points
+  #[arg(long, conflicts_with = "games", value_parser = parse_positive)]
+  pub score_ci: Option<f64>,
+  /// Simulates games until the 95% confidence interval of the mean number of turns is within this
+  /// many turns
+  #[arg(long, conflicts_with = "games", value_parser = parse_positive)]
+  pub turns_ci: Option<f64>,
+  /// With a precision target, simulates at least this many games [default: 1000]
+  #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+  pub min_games: Option<usize>,
+  /// With a precision target, simulates at most this many games
+  #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+  pub max_games: Option<usize>,
+  /// Master seed, from which the seed of each game is derived; random if not given
+  #[arg(long)]
+  pub seed: Option<u64>,
+  /// Number of worker threads; defaults to the number of available cores
+  #[arg(long)]
+  pub threads: Option<usize>,
+}
+
+/// A list of card ranks. The alias keeps clap from treating `Option<Vec<_>>` as a repeated argument.
+pub type Cards = Vec<u8>;
+
+/// Parses a comma-separated list of card ranks, where `RxC` stands for `C` copies of rank `R`.
+pub fn parse_cards(s: &str) -> Result<Cards, String> {
+  let mut cards = Vec::new();
+
+  for item in s.split(',').map(str::trim) {
+    if item.is_empty() {
+      return Err(format!("empty item in `{s}`"));
+    }
+    let (rank, copies) = match item.split_once('x') {
+      Some((rank, copies)) => (
+        rank,
+        copies
+          .parse()
+          .map_err(|_| format!("invalid count in `{item}`"))?,
+      ),
+      None => (item, 1),
+    };
+
+    let rank: u8 = rank
+      .parse()
+      .map_err(|_| format!("invalid rank in `{item}`"))?;
+    cards.extend([rank].repeat(copies));
+  }
+
+  if cards.is_empty() {
+    return Err("expected at least one card".to_string());
+  }
+
+  Ok(cards)
+}
+
+/// Formats card ranks in the syntax of `parse_cards`, e.g. `13x4,12`, grouping runs of equal ranks.
+pub fn format_cards(ranks: &[u8]) -> String {
+  let groups = ranks
+    .chunk_by(|a, b| a == b)
+    .map(|group| match group.len() {
+      1 => group[0].to_string(),
+      n => format!("{}x{n}", group[0]),
+    });
+  groups.collect::<Vec<_>>().join(",")
+}
+
+/// Parses a positive, finite number of seconds.
+pub fn parse_seconds(s: &str) -> Result<f64, String> {
+  let seconds: f64 = s.parse().map_err(|_| format!("invalid number `{s}`"))?;
+  if !(seconds > 0.0 && seconds.is_finite()) {
+    return Err(format!("`{s}` is not a positive number of seconds"));
+  }
+  Ok(seconds)
+}
+
+/// Parses a positive, finite number, such as a precision target.
+pub fn parse_positive(s: &str) -> Result<f64, String> {
+  let value: f64 = s.parse().map_err(|_| format!("invalid number `{s}`"))?;
+  if !(value > 0.0 && value.is_finite()) {
+    return Err(format!("`{s}` is not a positive number"));
+  }
+  Ok(value)
+}
+
+/// How game length histograms are plotted.
+#[derive(Args)]
+pub struct PlotArgs {
+  /// Title of the plot
+  #[arg(long, default_value = "Game Lengths")]
+  pub title: String,
+  /// Number of turns per bar
+  #[arg(long, default_value_t = 10, value_parser = RangedU64ValueParser::<u64>::new().range(1..))]
+  pub bin_width: u64,
+  /// Largest number of turns shown; defaults to the largest 99th percentile of the plotted games
+  #[arg(long, value_parser = RangedU64ValueParser::<u64>::new().range(1..))]
+  pub max_turns: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn cards_round_trip_through_their_syntax() {
+    let ranks = [13, 13, 13, 13, 12, 2, 2, 255];
+    assert_eq!(format_cards(&ranks), "13x4,12,2x2,255");
+    assert_eq!(parse_cards("13x4,12,2x2,255").unwrap(), ranks);
+    assert_eq!(parse_cards(" 13x4 , 12,2x2, 255 ").unwrap(), ranks);
+    assert_eq!(parse_cards("1,2,1").unwrap(), [1, 2, 1]);
+    assert_eq!(format_cards(&[1, 2, 1]), "1,2,1");
+  }
+
+  #[test]
+  fn malformed_cards_are_rejected() {
+    let error = |s| parse_cards(s).unwrap_err();
+    // Ranks must fit in a byte
+    assert_eq!(error("13,256"), "invalid rank in `256`");
+    assert_eq!(error("-1"), "invalid rank in `-1`");
+    assert_eq!(error("ace"), "invalid rank in `ace`");
+    // Cards are given by rank only, without a suit
+    assert_eq!(error("13S"), "invalid rank in `13S`");
+    assert_eq!(error("13xS"), "invalid count in `13xS`");
+    // Every item must be a card
+    assert_eq!(error("13,,12"), "empty item in `13,,12`");
+    assert_eq!(error("13,"), "empty item in `13,`");
+    assert_eq!(error(""), "empty item in ``");
+    assert_eq!(error("13x0"), "expected at least one card");
+  }
+}
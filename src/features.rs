@@ -0,0 +1,156 @@
+use crate::card::{Ranked, JOKER};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+/// A feature of a player's initial cards, which may predict how a game ends.
+#[derive(Clone, Copy)]
+pub enum Feature {
+  /// The sum of the ranks of the cards
+  RankSum,
+  /// The number of cards of the top rank of the deck, the aces of a standard deck
+  Aces,
+  /// The number of cards of the top four ranks of the deck (jack to ace in a standard deck), and
+  /// jokers
+  HighCards,
+}
+
+impl Feature {
+  pub const ALL: [Feature; 3] = [Feature::RankSum, Feature::Aces, Feature::HighCards];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::RankSum => "rank sum",
+      Self::Aces => "aces",
+      Self::HighCards => "high cards",
+    }
+  }
+
+  /// The value of the feature for `cards`, dealt from a deck whose highest rank (not counting
+  /// jokers) is `top_rank`. A joker counts as one rank above it.
+  pub fn value<C: Ranked>(self, cards: &[C], top_rank: u8) -> i64 {
+    let ranks = cards.iter().map(|card| match card.rank() {
+      JOKER => top_rank as i64 + 1,
+      rank => rank as i64,
+    });
+    let top_rank = top_rank as i64;
+
+    match self {
+      Self::RankSum => ranks.sum(),
+      Self::Aces => ranks.filter(|&rank| rank == top_rank).count() as i64,
+      Self::HighCards => ranks.filter(|&rank| rank > top_rank - 4).count() as i64,
+    }
+  }
+}
+
+/// Games grouped into bins of equal width by the value of a feature of player 1's initial cards.
+pub struct Bins {
+  width: i64,
+  /// The number of games, player 1's total score, and the total number of turns of each bin, by the
+  /// lowest value of the bin
+  bins: BTreeMap<i64, (usize, f64, f64)>,
+}
+
+impl Bins {
+  pub fn new(width: i64) -> Self {
+    Self {
+      width,
+      bins: BTreeMap::new(),
+    }
+  }
+
+  pub fn add(&mut self, value: i64, score: f64, turns: f64) {
+    let low = value.div_euclid(self.width) * self.width;
+    let bin = self.bins.entry(low).or_default();
+    bin.0 += 1;
+    bin.1 += score;
+    bin.2 += turns;
+  }
+
+  /// The values, number of games, player 1's mean score, and mean number of turns of each nonempty
+  /// bin, from the lowest values.
+  pub fn rows(&self) -> impl Iterator<Item = (RangeInclusive<i64>, usize, f64, f64)> + '_ {
+    self.bins.iter().map(|(&low, &(games, score, turns))| {
+      let n = games as f64;
+      (low..=low + self.width - 1, games, score / n, turns / n)
+    })
+  }
+}
+
+/// Formats the values of a bin, as a single value if it has only one.
+pub fn format_range(values: &RangeInclusive<i64>) -> String {
+  if values.start() == values.end() {
+    values.start().to_string()
+  } else {
+    format!("{} to {}", values.start(), values.end())
+  }
+}
+
+/// Writes the bins of every feature to a CSV file, with a row per bin.
+pub fn write_csv(path: &Path, features: &[(Feature, Bins)]) -> std::io::Result<()> {
+  let mut writer = BufWriter::new(File::create(path)?);
+  writeln!(writer, "feature,low,high,games,player1_score,mean_turns")?;
+
+  for (feature, bins) in features {
+    for (values, games, score, turns) in bins.rows() {
+      writeln!(
+        writer,
+        "{},{},{},{games},{score},{turns}",
+        feature.name(),
+        values.start(),
+        values.end()
+      )?;
+    }
+  }
+
+  writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn features_count_jokers_above_the_top_rank() {
+    let cards = [13, 13, 10, 9, JOKER, 2];
+    let value = |feature: Feature| feature.value(&cards, 13);
+    assert_eq!(value(Feature::RankSum), 13 + 13 + 10 + 9 + 14 + 2);
+    assert_eq!(value(Feature::Aces), 2);
+    assert_eq!(value(Feature::HighCards), 4);
+  }
+
+  #[test]
+  fn games_are_counted_into_bins_by_value() {
+    let mut bins = Bins::new(5);
+    bins.add(0, 1.0, 10.0);
+    bins.add(4, 0.0, 20.0);
+    bins.add(5, 0.5, 30.0);
+    bins.add(-1, 1.0, 40.0);
+
+    let rows: Vec<_> = bins.rows().collect();
+    assert_eq!(
+      rows,
+      [
+        (-5..=-1, 1, 1.0, 40.0),
+        (0..=4, 2, 0.5, 15.0),
+        (5..=9, 1, 0.5, 30.0),
+      ]
+    );
+    assert_eq!(format_range(&rows[1].0), "0 to 4");
+    assert_eq!(format_range(&(3..=3)), "3");
+
+    let path = std::env::temp_dir().join(format!("war-features-{}.csv", std::process::id()));
+    write_csv(&path, &[(Feature::Aces, bins)]).unwrap();
+    let contents = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+      contents,
+      "feature,low,high,games,player1_score,mean_turns\n\
+       aces,-5,-1,1,1,40\n\
+       aces,0,4,2,0.5,15\n\
+       aces,5,9,1,0.5,30\n"
+    );
+  }
+}
@@ -1,199 +1,515 @@
+mod card;
+mod cli;
+mod features;
+mod markov;
+mod plot;
+mod record;
+mod runner;
+mod scenario;
+mod search;
 mod sim;
+mod stats;
+mod strategy;
+mod trace;
 
+use card::{Card, Ranked, JOKER};
+use clap::{Parser, ValueEnum};
+use cli::{format_cards, Cli, Command, DeckArgs, HandicapRule, PlotArgs, RunArgs, SearchGoal};
+use comfy_table::presets::UTF8_FULL;
+use comfy_table::{Cell, Table};
 use fastrand::Rng;
-use sim::{Game, GameResult, Params, PlayerDeck};
+use features::{format_range, write_csv, Bins, Feature};
+use markov::Solver;
+use plot::PlotOptions;
+use record::{read_lengths, write_records, GameRecord};
+use runner::{
+  default_threads, game_seed, master_seed, new_game, play_games, record_games, Budget, RunOptions,
+};
+use scenario::{Deal, Suite};
+use search::Split;
+use sim::{GameResult, HonorRule, Params, Player, PlayerDeck, Refill, WarSize};
+use stats::{mean, mean_stddev, Estimate, Histogram, LinearFit};
+use std::cmp::Reverse;
+use std::fs::File;
+use std::io::BufWriter;
+use std::iter::once;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use strategy::LootStrategy;
 use thousands::Separable;
+use trace::JsonLines;
 
-/// Computes the mean of an iterator of f64s.
-fn mean(data: impl Iterator<Item = f64>, n: usize) -> f64 {
-  data.sum::<f64>() / n as f64
+/// The relative tolerance to which exact solutions are computed.
+const EXACT_TOLERANCE: f64 = 1e-12;
+/// The maximum number of states of an exact solution in the small games table.
+const EXACT_MAX_STATES: usize = 2_000_000;
+
+impl From<&PlotArgs> for PlotOptions {
+  fn from(args: &PlotArgs) -> Self {
+    Self {
+      title: args.title.clone(),
+      bin_width: args.bin_width,
+      max_turns: args.max_turns,
+    }
+  }
+}
+
+/// The run options of the command line, exiting with an error if they contradict each other.
+fn run_options(args: &RunArgs) -> RunOptions {
+  RunOptions::try_from(args).unwrap_or_else(|err| {
+    eprintln!("error: {err}");
+    std::process::exit(1);
+  })
+}
+
+/// The deal of the command line, exiting with an error if the deck cannot be dealt that way.
+fn deal(args: &DeckArgs) -> Deal {
+  Deal::from_args(args).unwrap_or_else(|err| {
+    eprintln!("error: {err}");
+    std::process::exit(1);
+  })
+}
+
+/// Summary statistics of a batch of simulated games.
+struct Summary {
+  /// The master seed, from which the seed of each game was derived
+  seed: u64,
+  n_games: usize,
+  elapsed: Duration,
+  /// The mean score of player 1, counting draws (and unfinished games) as an equal share of a win
+  score: Estimate,
+  /// The fraction of games that ended in a draw, with every remaining player out of cards
+  draws: Estimate,
+  turns: Estimate,
+  stddev_turns: f64,
+  /// The number of games of each length
+  histogram: Histogram,
+  /// The number of turns and the seed of the longest game
+  longest: (u64, u64),
+  /// The number of games stopped for reaching the turn limit
+  timeouts: usize,
+  /// The number of games stopped for returning to an earlier state
+  cycles: usize,
+  /// Whether the precision target was met, if there was one
+  precision_met: Option<bool>,
+  /// How each player finished, in games of more than two players
+  players: Vec<Finishes>,
 }
 
-/// Computes the mean and standard deviation of an iterator of f64s.
-fn mean_stddev(data: &[f64]) -> (f64, f64) {
-  let mu = mean(data.iter().copied(), data.len());
-  let variance = mean(data.iter().map(|&x| (x - mu).powi(2)), data.len());
-  (mu, variance.sqrt())
+/// How one player finished a batch of games.
+struct Finishes {
+  /// The fraction of games won
+  wins: f64,
+  /// The mean finishing place, from 1
+  mean_place: f64,
+  /// The mean turn on which the player was eliminated, over the games in which they were
+  mean_elimination: Option<f64>,
+}
+
+impl Finishes {
+  fn new(records: &[GameRecord], player: usize) -> Self {
+    let n_games = records.len();
+    let won = |record: &&GameRecord| record.result == GameResult::Win(Player(player));
+    let eliminations: Vec<_> = records
+      .iter()
+      .filter_map(|record| record.eliminations[player])
+      .collect();
+
+    Self {
+      wins: records.iter().filter(won).count() as f64 / n_games as f64,
+      mean_place: mean(
+        records.iter().map(|record| record.places()[player] as f64),
+        n_games,
+      ),
+      mean_elimination: (!eliminations.is_empty()).then(|| {
+        mean(
+          eliminations.iter().map(|&turn| turn as f64),
+          eliminations.len(),
+        )
+      }),
+    }
+  }
 }
 
-/// A standard deck of cards with `n` types, `k` copies each.
-fn standard_deck(n: u8, k: usize) -> Vec<u8> {
-  (1..=n).flat_map(|i| [i].repeat(k)).collect()
+impl Summary {
+  fn print(&self) {
+    println!(
+      "  {} games in {:?}",
+      self.n_games.separate_with_commas(),
+      self.elapsed
+    );
+    println!(
+      "  mean score: Player 1 wins {:.1}% (95% CI: {:.1}% to {:.1}%)",
+      100.0 * self.score.value,
+      100.0 * self.score.lower,
+      100.0 * self.score.upper
+    );
+    println!(
+      "  draws: {:.2}% (95% CI: {:.2}% to {:.2}%)",
+      100.0 * self.draws.value,
+      100.0 * self.draws.lower,
+      100.0 * self.draws.upper
+    );
+    println!(
+      "  mean turns: {:.2} +/- {:.2} (95% CI; standard error {:.2}), stddev {:.2}",
+      self.turns.value,
+      self.turns.half_width(),
+      self.turns.standard_error,
+      self.stddev_turns
+    );
+    println!(
+      "  quantiles: median {}, p90 {}, p99 {}, max {} turns",
+      self.histogram.median().separate_with_commas(),
+      self.histogram.quantile(0.9).separate_with_commas(),
+      self.histogram.quantile(0.99).separate_with_commas(),
+      self.histogram.max().separate_with_commas()
+    );
+    println!(
+      "  longest game: {} turns (seed {}, master seed {})",
+      self.longest.0, self.longest.1, self.seed
+    );
+
+    if self.timeouts > 0 || self.cycles > 0 {
+      println!(
+        "  unfinished: {} timeouts, {} cycles (scored as draws)",
+        self.timeouts.separate_with_commas(),
+        self.cycles.separate_with_commas()
+      );
+    }
+
+    if self.precision_met == Some(false) {
+      println!("  target precision not reached");
+    }
+
+    for (i, finishes) in self.players.iter().enumerate() {
+      let elimination = finishes
+        .mean_elimination
+        .map_or("never eliminated".to_string(), |turn| {
+          format!("eliminated on turn {turn:.1} on average")
+        });
+      println!(
+        "  player {}: wins {:.1}%, mean place {:.2}, {elimination}",
+        i + 1,
+        100.0 * finishes.wins,
+        finishes.mean_place
+      );
+    }
+  }
 }
 
-/// Simulates a bunch of games using the given function to determine the player's initial decks.
-/// If a path is given, saves a list of the individual game lengths as a json file.
-fn simulate<F>(path: Option<&str>, params: Params, f: F)
-where
-  F: Fn(&mut Rng) -> (PlayerDeck, PlayerDeck),
-{
+/// Simulates a bunch of games dealt by the given deal.
+/// If a path is given, saves a record of every game to it (see `write_records`).
+/// Each game is seeded by `game_seed` from the master seed, so that it may be replayed individually.
+fn simulate(path: Option<&Path>, params: Params, options: RunOptions, deal: &Deal) -> Summary {
+  // Games are played with bare ranks, unless suits matter to the rules
+  let ranks = |rng: &mut Rng| deal.deal::<u8>(rng);
+  let cards = |rng: &mut Rng| deal.deal::<Card>(rng);
+  let n_players = deal.players();
   // Simulate
   let start = std::time::Instant::now();
 
-  let mut rng = Rng::new();
-  let mut wins = Vec::new();
-  let mut turns = Vec::new();
+  let seed = master_seed(options.seed);
+  let mut results = Vec::new();
+  let mut records = Vec::new();
 
-  // Simulate games until at least a second has elapsed
-  let mut n_games = 900usize;
-  while start.elapsed().as_secs_f64() <= 1.0 {
-    n_games += 10usize.pow(n_games.ilog10());
+  // Games are only observed when their records are saved or needed for the finishing places of more
+  // than two players, so that other runs are not slowed down
+  let mut play = |results: &mut Vec<_>, n_games: usize| {
+    let indices = results.len()..n_games;
+    let threads = options.threads;
+    if path.is_some() || n_players > 2 {
+      let new_records = if params.uses_suits() {
+        record_games(&params, seed, indices, threads, &cards)
+      } else {
+        record_games(&params, seed, indices, threads, &ranks)
+      };
+      results.extend(
+        new_records
+          .iter()
+          .map(|record| (record.result, record.turns)),
+      );
+      records.extend(new_records);
+    } else {
+      results.extend(if params.uses_suits() {
+        play_games(&params, seed, indices, threads, &cards)
+      } else {
+        play_games(&params, seed, indices, threads, &ranks)
+      });
+    }
+  };
 
-    while wins.len() < n_games {
-      let (player1, player2) = f(&mut rng);
-      let mut game = Game::new(params, rng.fork(), player1, player2);
-      let (win, turn) = game.play();
+  let mut precision_met = None;
+  let n_games = match options.budget {
+    Budget::Games(n_games) => {
+      play(&mut results, n_games);
+      n_games
+    }
 
-      wins.push(win);
-      turns.push(turn);
+    // Simulate games until enough time has elapsed, at least one batch of them
+    Budget::Time(duration) => {
+      let mut n_games = 900usize;
+      loop {
+        n_games += 10usize.pow(n_games.ilog10());
+        play(&mut results, n_games);
+        if start.elapsed() > duration {
+          break results.len();
+        }
+      }
     }
-  }
+
+    // Simulate games until the confidence intervals are narrow enough, or a limit is reached
+    Budget::Precision(precision) => {
+      let mut n_games = precision.min_games.max(1);
+      loop {
+        play(&mut results, n_games);
+
+        let (score, turns, _) = estimates(&results, n_players);
+        let met = precision
+          .score
+          .is_none_or(|target| score.half_width() <= target)
+          && precision
+            .turns
+            .is_none_or(|target| turns.half_width() <= target);
+
+        let out_of_games = precision
+          .max_games
+          .is_some_and(|max_games| n_games >= max_games);
+        let out_of_time = precision.time.is_some_and(|time| start.elapsed() > time);
+        if met || out_of_games || out_of_time {
+          precision_met = Some(met);
+          break n_games;
+        }
+
+        n_games += 10usize.pow(n_games.ilog10());
+        if let Some(max_games) = precision.max_games {
+          n_games = n_games.min(max_games);
+        }
+      }
+    }
+  };
+
+  let (score, turns, stddev_turns) = estimates(&results, n_players);
+  let (wins, turns_played): (Vec<_>, Vec<_>) = results.into_iter().unzip();
 
   let elapsed = start.elapsed();
 
   // Write data, if requested
   if let Some(path) = path {
-    let string = serde_json::to_string(&turns).unwrap();
-    std::fs::write(path, string).unwrap();
+    write_records(path, &records).unwrap();
   }
 
+  let players = if n_players > 2 {
+    (0..n_players)
+      .map(|player| Finishes::new(&records, player))
+      .collect()
+  } else {
+    Vec::new()
+  };
+
   // Statistics
+  let longest = turns_played
+    .iter()
+    .enumerate()
+    .max_by_key(|&(_, &turns)| turns)
+    .map_or((0, 0), |(i, &turns)| (turns, game_seed(seed, i as u64)));
+
+  let count = |result: GameResult| wins.iter().filter(|&&win| win == result).count();
+  let timeouts = count(GameResult::Timeout);
+  let cycles = count(GameResult::Cycle);
+  let draws = Estimate::proportion(count(GameResult::Draw) as f64 / n_games as f64, n_games);
+  let histogram = Histogram::new(turns_played);
+
+  Summary {
+    seed,
+    n_games,
+    elapsed,
+    score,
+    draws,
+    turns,
+    stddev_turns,
+    histogram,
+    longest,
+    timeouts,
+    cycles,
+    precision_met,
+    players,
+  }
+}
+
+/// Player 1's score of a game of `n_players` players: 1 for a win, and an equal share of a win for a
+/// draw. Unfinished games are scored like draws, since no player won.
+fn score(result: GameResult, n_players: usize) -> f64 {
+  match result {
+    GameResult::Win(Player(0)) => 1.0,
+    GameResult::Win(_) => 0.0,
+    GameResult::Draw | GameResult::Timeout | GameResult::Cycle => 1.0 / n_players as f64,
+  }
+}
+
+/// Estimates player 1's mean score and the mean number of turns from the results of some games of
+/// `n_players` players, also returning the standard deviation of the number of turns.
+fn estimates(results: &[(GameResult, u64)], n_players: usize) -> (Estimate, Estimate, f64) {
+  let n_games = results.len();
   let mean_score = mean(
-    wins.into_iter().map(|win| match win {
-      GameResult::Player1 => 1.0,
-      GameResult::Player2 => 0.0,
-      GameResult::Draw => 0.5,
-    }),
+    results.iter().map(|&(result, _)| score(result, n_players)),
     n_games,
   );
 
-  let turns: Vec<_> = turns.into_iter().map(|x| x as f64).collect();
+  let turns: Vec<_> = results.iter().map(|&(_, turns)| turns as f64).collect();
   let (mean_turns, stddev_turns) = mean_stddev(&turns);
 
-  println!("  {} games in {elapsed:?}", n_games.separate_with_commas());
-  println!("  mean score: Player 1 wins {:.1}%", 100.0 * mean_score);
-  println!("  mean turns: {mean_turns:.2} +/- {stddev_turns:.2}");
+  (
+    Estimate::proportion(mean_score, n_games),
+    Estimate::mean(mean_turns, stddev_turns, n_games),
+    stddev_turns,
+  )
 }
 
-/// Simulates a large number of games of a few game setups, and prints out information about them.
-/// Additionally writes out `standard_war.json` and `honorable_war.json`, list of empirical game lengths.
-fn standard_games() {
-  println!("Standard war (shuffled):");
-  simulate(Some("standard_war.json"), Params::default(), |rng| {
-    let mut deck = standard_deck(13, 4);
-    rng.shuffle(&mut deck);
-
-    let player1 = PlayerDeck::new(deck[..26].to_vec());
-    let player2 = PlayerDeck::new(deck[26..].to_vec());
-    (player1, player2)
-  });
+/// Runs every scenario of a suite in order, printing the summary of each,
+/// then plots the game lengths of the suite's plotted scenarios, if any.
+fn run_suite(suite: &Suite, seed: Option<u64>, threads: usize) {
+  let mut histograms = Vec::new();
 
-  println!();
-  println!("Standard war (evenly split):");
-  simulate(None, Params::default(), |_| {
-    let player1 = PlayerDeck::new(standard_deck(13, 2));
-    let player2 = PlayerDeck::new(standard_deck(13, 2));
-    (player1, player2)
-  });
+  for (i, scenario) in suite.scenarios.iter().enumerate() {
+    if i > 0 {
+      println!();
+    }
 
-  println!();
-  println!("2-deck war (evenly split):");
-  simulate(None, Params::default(), |_| {
-    let player1 = PlayerDeck::new(standard_deck(13, 4));
-    let player2 = PlayerDeck::new(standard_deck(13, 4));
-    (player1, player2)
-  });
+    println!("{}:", scenario.name);
+    let summary = simulate(
+      scenario.output.as_deref(),
+      scenario.params(),
+      scenario.run_options(seed, threads),
+      &scenario.deal(),
+    );
+    summary.print();
+    histograms.push(summary.histogram);
+  }
 
-  println!();
-  println!("12-deck war (evenly split):");
-  simulate(None, Params::default(), |_| {
-    let player1 = PlayerDeck::new(standard_deck(13, 4 * 6));
-    let player2 = PlayerDeck::new(standard_deck(13, 4 * 6));
-    (player1, player2)
-  });
+  if let Some(plot) = &suite.plot {
+    let series: Vec<_> = plot
+      .scenarios
+      .iter()
+      .map(|name| {
+        let i = suite.scenarios.iter().position(|s| &s.name == name);
+        (name.as_str(), &histograms[i.unwrap()])
+      })
+      .collect();
 
-  println!();
-  println!("Aces vs. the world:");
-  simulate(None, Params::default(), |_| {
-    let player1 = PlayerDeck::new([13].repeat(4).to_vec());
-    let player2 = PlayerDeck::new((1..=12).flat_map(|i| [i].repeat(4)).collect());
-    (player1, player2)
-  });
+    write_plot(&plot.output, &series, &plot.options());
+  }
+}
 
+/// Plots game length histograms to an SVG file.
+fn write_plot(path: &Path, series: &[(&str, &Histogram)], options: &PlotOptions) {
+  std::fs::write(path, plot::game_lengths(series, options)).unwrap();
   println!();
-  println!("Honorable war (shuffled):");
-  simulate(Some("honorable_war.json"), Params::new(3, 1), |rng| {
-    let mut deck = standard_deck(13, 4);
-    rng.shuffle(&mut deck);
+  println!("Game lengths plotted to {}", path.display());
+}
 
-    let player1 = PlayerDeck::new(deck[..26].to_vec());
-    let player2 = PlayerDeck::new(deck[26..].to_vec());
-    (player1, player2)
-  });
+/// Plots the game lengths saved by `simulate` to JSON Lines or CSV files.
+fn plot_files(files: &[PathBuf], labels: &[String], output: &Path, options: &PlotOptions) {
+  let histograms: Vec<_> = files
+    .iter()
+    .map(|path| {
+      let lengths = read_lengths(path).unwrap_or_else(|err| {
+        eprintln!("error: {}: {err}", path.display());
+        std::process::exit(1);
+      });
+      Histogram::new(lengths)
+    })
+    .collect();
 
-  println!();
-  println!("2-deck Honorable war (evenly split):");
-  simulate(None, Params::new(3, 1), |_| {
-    let player1 = PlayerDeck::new(standard_deck(13, 4));
-    let player2 = PlayerDeck::new(standard_deck(13, 4));
-    (player1, player2)
-  });
+  let labels: Vec<_> = files
+    .iter()
+    .enumerate()
+    .map(|(i, path)| {
+      labels.get(i).cloned().unwrap_or_else(|| {
+        let stem = path.file_stem().unwrap_or(path.as_os_str());
+        stem.to_string_lossy().into_owned()
+      })
+    })
+    .collect();
 
-  println!();
-  println!("12-deck Honorable war (evenly split):");
-  simulate(None, Params::new(3, 1), |_| {
-    let player1 = PlayerDeck::new(standard_deck(13, 4 * 6));
-    let player2 = PlayerDeck::new(standard_deck(13, 4 * 6));
-    (player1, player2)
-  });
+  let series: Vec<_> = labels.iter().map(String::as_str).zip(&histograms).collect();
+  write_plot(output, &series, options);
+}
 
-  println!();
-  println!("12-deck Doubly-honorable war (evenly split):");
-  simulate(None, Params::new(3, 2), |_| {
-    let player1 = PlayerDeck::new(standard_deck(13, 4 * 6));
-    let player2 = PlayerDeck::new(standard_deck(13, 4 * 6));
-    (player1, player2)
-  });
+/// Simulates a large number of games of a few game setups (`scenarios/standard.toml`), and prints
+/// out information about them.
+/// Additionally writes out `standard_war.jsonl` and `honorable_war.jsonl`, records of every game,
+/// and plots them to `game-lengths.svg`.
+fn standard_games() {
+  let suite = Suite::from_toml(include_str!("../scenarios/standard.toml")).unwrap();
+  run_suite(&suite, None, default_threads());
 }
 
 /// Simulates a large number of small-deck games with various number of flipped cards
-/// in a war, and pretty-prints them in a well-formatted table
-fn small_games() {
-  let n_games = 100_000;
-
-  use comfy_table::presets::UTF8_FULL;
-  use comfy_table::{Cell, Table};
-
-  use std::iter::once;
-
+/// in a war, and pretty-prints them in a well-formatted table.
+/// If the war size depends on the tied rank, the table has a single column instead of one per `k`.
+/// Only a budget of a number of games is supported, so that every cell has the same precision.
+fn small_games(
+  ns: impl Iterator<Item = u8>,
+  ks: impl Iterator<Item = usize>,
+  war_size: WarSize,
+  honor_threshold: u8,
+  exact_max_n: u8,
+  options: RunOptions,
+) {
   /// Simulates a bunch of games where each player has `n` unique cards and `k` cards are flipped
-  /// in a war, returning the av
-  fn simulate(n_games: usize, n: u8, k: usize) -> f64 {
-    let mut rng = Rng::new();
+  /// in a war, returning the average number of turns
+  fn simulate(seed: u64, threads: usize, n_games: usize, n: u8, params: Params) -> Estimate {
     let deck = PlayerDeck::new((0..n).collect());
 
-    let turns = (0..n_games).map(|_| {
-      let mut game = Game::new(Params::new(k, 0), rng.fork(), deck.clone(), deck.clone());
-      let (_, turns) = game.play();
-      turns as f64
+    let results = play_games(&params, seed, 0..n_games, threads, &|_| {
+      vec![deck.clone(), deck.clone()]
     });
 
-    mean(turns, n_games)
+    let turns: Vec<_> = results.into_iter().map(|(_, turns)| turns as f64).collect();
+    let (mean_turns, stddev_turns) = mean_stddev(&turns);
+    Estimate::mean(mean_turns, stddev_turns, n_games)
   }
 
+  let Budget::Games(n_games) = options.budget else {
+    panic!("small games need a number of games");
+  };
+  let (seed, threads) = (master_seed(options.seed), options.threads);
+
   // Draw table
   let mut table = Table::new();
   table.load_preset(UTF8_FULL);
 
-  let ns = 1..=13;
-  let ks = 0..10;
-
-  table.set_header(once(Cell::new("n/k")).chain(ks.clone().map(Cell::new)));
+  let (ks, header): (Vec<_>, _) = match war_size {
+    WarSize::Rank => (vec![0], vec![Cell::new("n"), Cell::new("rank")]),
+    _ => {
+      let ks: Vec<_> = ks.collect();
+      let header = once(Cell::new("n/k"))
+        .chain(ks.iter().map(Cell::new))
+        .collect();
+      (ks, header)
+    }
+  };
+  table.set_header(header);
 
   for n in ns {
-    let row = ks.clone().map(|k| {
-      let turns = simulate(n_games, n, k);
-      format!("{:.1}", turns)
+    let row = ks.iter().map(|&k| {
+      // A cell too large to solve exactly is simulated instead
+      let exact = (n <= exact_max_n).then(|| {
+        let hand = card::from_ranks(&(0..n).collect::<Vec<_>>());
+        let deal = Deal::Fixed(vec![hand.clone(), hand]);
+        Solver::new(&deal, k, war_size, honor_threshold)
+          .and_then(|solver| solver.solve(&deal, EXACT_MAX_STATES, EXACT_TOLERANCE))
+          .inspect_err(|err| eprintln!("n = {n}, k = {k}: {err}; simulating instead"))
+      });
+
+      match exact {
+        Some(Ok(solution)) => format!("{:.1}", solution.mean_turns),
+        _ => {
+          let params = Params::new(k, honor_threshold).with_war_size(war_size);
+          let turns = simulate(seed, threads, n_games, n, params);
+          format!("{:.1} +/- {:.1}", turns.value, turns.half_width())
+        }
+      }
     });
 
     table.add_row(once(format!("{n}")).chain(row));
@@ -201,12 +517,752 @@ fn small_games() {
 
   println!();
   println!("Small games:");
-  println!("  Each player has deck of n unique cards, and k cards are flipped faced-down in a war");
-  println!("  games per cell: {n_games}");
+  let war = match war_size {
+    WarSize::Fixed => "k cards are flipped face-down in a war",
+    WarSize::Rank => "as many cards as the number on the tied cards (from 1 for the lowest, up to 10) are flipped face-down in a war",
+    WarSize::Escalating => {
+      "k cards are flipped face-down in the first war of a round, and one more in each further war"
+    }
+  };
+  println!("  Each player has deck of n unique cards, and {war}");
+  println!("  games per cell: {n_games}, with 95% confidence intervals");
+  if exact_max_n > 0 {
+    println!("  cells with n <= {exact_max_n} are exact, unless they have too many states");
+  }
+  println!("  master seed: {seed}");
+  println!("{table}");
+}
+
+/// Computes the exact win probabilities and expected length of a game, and prints them,
+/// optionally next to the estimates of `n_games` simulated games.
+fn exact(
+  deal: &Deal,
+  k: usize,
+  war_size: WarSize,
+  honor_threshold: u8,
+  max_states: usize,
+  n_games: Option<usize>,
+  seed: Option<u64>,
+) {
+  let start = std::time::Instant::now();
+  let solution = Solver::new(deal, k, war_size, honor_threshold)
+    .and_then(|solver| solver.solve(deal, max_states, EXACT_TOLERANCE))
+    .unwrap_or_else(|err| {
+      eprintln!("error: {err}");
+      std::process::exit(1);
+    });
+
+  println!(
+    "  {} states solved in {:?}",
+    solution.n_states.separate_with_commas(),
+    start.elapsed()
+  );
+  println!(
+    "  exact mean score: Player 1 wins {:.4}% (Player 1 {:.4}%, Player 2 {:.4}%, draws {:.4}%)",
+    100.0 * solution.mean_score(),
+    100.0 * solution.player1,
+    100.0 * solution.player2,
+    100.0 * solution.draw
+  );
+  println!("  exact mean turns: {:.4}", solution.mean_turns);
+
+  if let Some(n_games) = n_games {
+    let options = RunOptions {
+      budget: Budget::Games(n_games),
+      seed,
+      ..RunOptions::default()
+    };
+
+    println!();
+    println!("Simulated:");
+    let params = Params::new(k, honor_threshold).with_war_size(war_size);
+    simulate(None, params, options, deal).print();
+  }
+}
+
+/// The master seed of a comparison of several setups, drawn at random if not given, and the run
+/// options fixed to it. Every setup is simulated with the same master seed, so that they are
+/// compared on the same deals.
+fn fixed_seed(options: RunOptions) -> (u64, RunOptions) {
+  let seed = master_seed(options.seed);
+  let options = RunOptions {
+    seed: Some(seed),
+    ..options
+  };
+  (seed, options)
+}
+
+/// Formats player 1's mean score and its 95% confidence interval as percentages.
+fn format_score(score: &Estimate) -> String {
+  format!(
+    "{:.1}% ({:.1}% to {:.1}%)",
+    100.0 * score.value,
+    100.0 * score.lower,
+    100.0 * score.upper
+  )
+}
+
+/// Simulates a single deck setup under every combination of the given rule parameters
+/// (see `fixed_seed`), and pretty-prints the results in a table.
+/// Without an honor threshold, the honor rules are all the same, so only one row is simulated.
+fn compare(
+  deal: &Deal,
+  ks: &[usize],
+  honor_thresholds: &[u8],
+  honor_rules: &[HonorRule],
+  options: RunOptions,
+) {
+  let (seed, options) = fixed_seed(options);
+
+  let mut table = Table::new();
+  table.load_preset(UTF8_FULL);
+  table.set_header([
+    "k",
+    "honor threshold",
+    "honor rule",
+    "games",
+    "Player 1 wins (95% CI)",
+    "mean turns (95% CI)",
+    "stddev turns",
+  ]);
+
+  for &k in ks {
+    for &honor_threshold in honor_thresholds {
+      let rules = match honor_threshold {
+        0 => &honor_rules[..1],
+        _ => honor_rules,
+      };
+
+      for &honor_rule in rules {
+        let params = Params::new(k, honor_threshold).with_honor_rule(honor_rule);
+        let summary = simulate(None, params, options, deal);
+        let honor_rule = match honor_threshold {
+          0 => "-".to_string(),
+          _ => honor_rule
+            .to_possible_value()
+            .unwrap()
+            .get_name()
+            .to_string(),
+        };
+
+        table.add_row([
+          k.to_string(),
+          honor_threshold.to_string(),
+          honor_rule,
+          summary.n_games.separate_with_commas(),
+          format_score(&summary.score),
+          format!(
+            "{:.2} +/- {:.2}",
+            summary.turns.value,
+            summary.turns.half_width()
+          ),
+          format!("{:.2}", summary.stddev_turns),
+        ]);
+      }
+    }
+  }
+
+  println!("  master seed: {seed}");
   println!("{table}");
 }
 
+/// Plays every pair of the given loot strategies against each other under the given rules (see
+/// `fixed_seed`), and pretty-prints player 1's mean score in a table, with a row for each strategy
+/// of player 1 and a column for each strategy of player 2.
+fn head_to_head(deal: &Deal, params: Params, strategies: &[LootStrategy], options: RunOptions) {
+  let (seed, options) = fixed_seed(options);
+  let name = |strategy: LootStrategy| strategy.to_possible_value().unwrap().get_name().to_string();
+
+  let mut table = Table::new();
+  table.load_preset(UTF8_FULL);
+  table
+    .set_header(once("player 1/player 2".to_string()).chain(strategies.iter().map(|&s| name(s))));
+
+  for &first in strategies {
+    let row = strategies.iter().map(|&second| {
+      let params = params.clone().with_loot_strategies(vec![first, second]);
+      format_score(&simulate(None, params, options, deal).score)
+    });
+
+    table.add_row(once(name(first)).chain(row));
+  }
+
+  println!("Head-to-head loot strategies:");
+  println!(
+    "  Player 1's mean score, with 95% confidence intervals (unfinished games are scored as draws)"
+  );
+  println!("  master seed: {seed}");
+  println!("{table}");
+}
+
+/// Simulates a deck setup under every value of a handicap of `player` from `values` (see
+/// `fixed_seed`), and pretty-prints the results in a table, followed by the value that makes
+/// player 1's mean score closest to even.
+fn handicap(
+  deal: &Deal,
+  params: Params,
+  player: Player,
+  rule: HandicapRule,
+  values: impl Iterator<Item = usize>,
+  options: RunOptions,
+) {
+  let (seed, options) = fixed_seed(options);
+  let rule_name = rule.to_possible_value().unwrap().get_name().to_string();
+
+  let mut table = Table::new();
+  table.load_preset(UTF8_FULL);
+  table.set_header([
+    rule_name.as_str(),
+    "games",
+    "Player 1 wins (95% CI)",
+    "mean turns (95% CI)",
+  ]);
+
+  let players = deal.players();
+  let mut most_even: Option<(usize, f64)> = None;
+  for value in values {
+    let params = match rule {
+      HandicapRule::K => params.clone().with_k_for(player, players, value),
+      HandicapRule::HonorThreshold => {
+        // Thresholds that do not fit were rejected before the sweep
+        let threshold = u8::try_from(value).unwrap();
+        params
+          .clone()
+          .with_honor_threshold_for(player, players, threshold)
+      }
+    };
+    let summary = simulate(None, params, options, deal);
+
+    let score = summary.score.value;
+    if most_even.is_none_or(|(_, best)| (score - 0.5).abs() < (best - 0.5).abs()) {
+      most_even = Some((value, score));
+    }
+
+    table.add_row([
+      value.to_string(),
+      summary.n_games.separate_with_commas(),
+      format_score(&summary.score),
+      format!(
+        "{:.2} +/- {:.2}",
+        summary.turns.value,
+        summary.turns.half_width()
+      ),
+    ]);
+  }
+
+  println!("Handicap of player {}:", player.0 + 1);
+  println!("  master seed: {seed}");
+  println!("{table}");
+  if let Some((value, score)) = most_even {
+    println!(
+      "  most even: {rule_name} = {value} (player 1 wins {:.1}%)",
+      100.0 * score
+    );
+  }
+}
+
+/// Searches for the split of a deck between two players that best meets `goal`, estimating every
+/// split tried from `step_games` games (see `fixed_seed`). Prints the splits found, then estimates
+/// the best one again with `options`.
+fn search(
+  deal: &Deal,
+  params: Params,
+  goal: SearchGoal,
+  steps: usize,
+  step_games: usize,
+  options: RunOptions,
+) {
+  let (seed, options) = fixed_seed(options);
+  let step_options = RunOptions {
+    budget: Budget::Games(step_games),
+    ..options
+  };
+  let estimate = |split: &Split| {
+    simulate(
+      None,
+      params.clone(),
+      step_options,
+      &Deal::Fixed(split.to_vec()),
+    )
+  };
+  let hand = |cards: &[Card]| {
+    let mut ranks: Vec<_> = cards.iter().map(|card| card.rank).collect();
+    ranks.sort_unstable_by(|a, b| b.cmp(a));
+    format_cards(&ranks)
+  };
+
+  let deck = deal.cards();
+  let mut rng = Rng::with_seed(seed);
+  println!("  master seed: {seed}");
+
+  let best = match goal {
+    SearchGoal::SmallestWinner => {
+      let mut tried =
+        search::smallest_winner(&deck, steps, &mut rng, |split| estimate(split).score.value);
+      tried.sort_by_key(|(split, _)| Reverse(split[0].len()));
+
+      let mut table = Table::new();
+      table.load_preset(UTF8_FULL);
+      table.set_header(["cards", "player 1's hand", "Player 1 wins"]);
+      for (split, score) in &tried {
+        table.add_row([
+          split[0].len().to_string(),
+          hand(&split[0]),
+          format!("{:.1}%", 100.0 * score),
+        ]);
+      }
+      println!("{table}");
+
+      let winners = tried.into_iter().filter(|&(_, score)| score >= 0.5);
+      let Some((split, _)) = winners.min_by_key(|(split, _)| split[0].len()) else {
+        println!("  no hand of half the deck or less wins half of the games");
+        return;
+      };
+      println!(
+        "  smallest winning hand: {} cards ({})",
+        split[0].len(),
+        hand(&split[0])
+      );
+      split
+    }
+
+    SearchGoal::Longest => {
+      let start = search::random(&deck, deck.len() / 2, &mut rng);
+      println!(
+        "  random split: {:.2} mean turns",
+        estimate(&start).turns.value
+      );
+      let (split, turns) =
+        search::climb(start, steps, &mut rng, |split| estimate(split).turns.value);
+      println!("  longest split: {turns:.2} mean turns");
+      println!("    player 1: {}", hand(&split[0]));
+      println!("    player 2: {}", hand(&split[1]));
+      split
+    }
+  };
+
+  println!();
+  println!("Best split:");
+  simulate(None, params, options, &Deal::Fixed(best.to_vec())).print();
+}
+
+/// Simulates games, and reports how features of player 1's initial cards predict player 1's score
+/// and the length of a game: the mean of both for the games binned by each feature (by `rank_sum_bin`
+/// for the rank sum), and the slope and correlation of a linear fit of each to each feature. If a
+/// path is given, also writes the binned means to it as CSV.
+fn features(
+  deal: &Deal,
+  params: Params,
+  rank_sum_bin: i64,
+  options: RunOptions,
+  output: Option<&Path>,
+) {
+  let Budget::Games(n_games) = options.budget else {
+    panic!("feature reports need a number of games");
+  };
+  let seed = master_seed(options.seed);
+  let n_players = deal.players();
+
+  let ranks = |rng: &mut Rng| deal.deal::<u8>(rng);
+  let cards = |rng: &mut Rng| deal.deal::<Card>(rng);
+  let indices = 0..n_games;
+  let results = if params.uses_suits() {
+    play_games(&params, seed, indices, options.threads, &cards)
+  } else {
+    play_games(&params, seed, indices, options.threads, &ranks)
+  };
+  let scores: Vec<_> = results
+    .iter()
+    .map(|&(result, _)| score(result, n_players))
+    .collect();
+  let turns: Vec<_> = results.iter().map(|&(_, turns)| turns as f64).collect();
+
+  // Every game deals its initial cards from its own seed, before it is played
+  let hands: Vec<Vec<u8>> = (0..n_games)
+    .map(|i| {
+      let mut rng = Rng::with_seed(game_seed(seed, i as u64));
+      deal.deal_hands(&mut rng).swap_remove(0)
+    })
+    .collect();
+  let top_rank = deal
+    .ranks()
+    .into_iter()
+    .filter(|&rank| rank != JOKER)
+    .max()
+    .unwrap_or(0);
+
+  println!(
+    "  {} games, master seed {seed}",
+    n_games.separate_with_commas()
+  );
+
+  let mut fits = Table::new();
+  fits.load_preset(UTF8_FULL);
+  fits.set_header([
+    "feature",
+    "win rate per unit",
+    "correlation with score",
+    "turns per unit",
+    "correlation with turns",
+  ]);
+
+  let mut binned = Vec::new();
+  for feature in Feature::ALL {
+    let values: Vec<_> = hands
+      .iter()
+      .map(|hand| feature.value(hand, top_rank))
+      .collect();
+    let width = match feature {
+      Feature::RankSum => rank_sum_bin,
+      Feature::Aces | Feature::HighCards => 1,
+    };
+    let mut bins = Bins::new(width);
+    for ((&value, &score), &turns) in values.iter().zip(&scores).zip(&turns) {
+      bins.add(value, score, turns);
+    }
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header([
+      format!("player 1's {}", feature.name()),
+      "games".to_string(),
+      "Player 1 wins (95% CI)".to_string(),
+      "mean turns".to_string(),
+    ]);
+    for (values, games, score, turns) in bins.rows() {
+      let score = Estimate::proportion(score, games);
+      table.add_row([
+        format_range(&values),
+        games.separate_with_commas(),
+        format_score(&score),
+        format!("{turns:.2}"),
+      ]);
+    }
+    println!("{table}");
+
+    let values: Vec<_> = values.into_iter().map(|value| value as f64).collect();
+    let score_fit = LinearFit::new(&values, &scores);
+    let turns_fit = LinearFit::new(&values, &turns);
+    fits.add_row([
+      feature.name().to_string(),
+      format!("{:+.2}%", 100.0 * score_fit.slope),
+      format!("{:+.3}", score_fit.correlation),
+      format!("{:+.2}", turns_fit.slope),
+      format!("{:+.3}", turns_fit.correlation),
+    ]);
+    binned.push((feature, bins));
+  }
+
+  println!("Linear fits:");
+  println!("{fits}");
+
+  if let Some(path) = output {
+    write_csv(path, &binned).unwrap();
+  }
+}
+
+/// Replays a single game from its seed, as reported by `simulate`.
+/// If a path is given, writes every event of the game to it as JSON Lines (`-` for stdout).
+fn replay(deal: &Deal, params: Params, seed: u64, trace: Option<&Path>) {
+  let (result, turns) = replay_game(deal, &params, seed, trace);
+
+  let result = match result {
+    GameResult::Win(Player(i)) => format!("Player {} wins", i + 1),
+    GameResult::Draw => "Draw".to_string(),
+    GameResult::Timeout => "Stopped at the turn limit".to_string(),
+    GameResult::Cycle => "Stopped in a cycle".to_string(),
+  };
+  println!("  {result} after {turns} turns");
+}
+
+/// Plays a single game from its seed like `replay`, returning its result and number of turns.
+/// The game is played with the same cards as `simulate` plays it, bare ranks unless suits matter to
+/// the rules, since cycles are detected by the identity of the cards: with suits, a game would only
+/// return to an earlier state once the suits of the cards did too.
+fn replay_game(deal: &Deal, params: &Params, seed: u64, trace: Option<&Path>) -> (GameResult, u64) {
+  fn play<C: Ranked>(
+    deal: &Deal,
+    params: &Params,
+    seed: u64,
+    trace: Option<&Path>,
+  ) -> (GameResult, u64) {
+    let mut game = new_game(params, seed, &|rng: &mut Rng| deal.deal::<C>(rng));
+
+    match trace {
+      None => game.play(),
+      Some(path) if path == Path::new("-") => {
+        game.play_observed(&mut JsonLines::new(std::io::stdout().lock()))
+      }
+      Some(path) => {
+        let file = BufWriter::new(File::create(path).unwrap());
+        game.play_observed(&mut JsonLines::new(file))
+      }
+    }
+  }
+
+  if params.uses_suits() {
+    play::<Card>(deal, params, seed, trace)
+  } else {
+    play::<u8>(deal, params, seed, trace)
+  }
+}
+
 fn main() {
-  standard_games();
-  small_games();
+  let cli = Cli::parse();
+
+  match cli.command {
+    None => {
+      standard_games();
+      let options = RunOptions {
+        budget: Budget::Games(100_000),
+        ..RunOptions::default()
+      };
+      small_games(1..=13, 0..10, WarSize::Fixed, 0, 0, options);
+    }
+
+    Some(Command::Simulate {
+      deck,
+      params,
+      run,
+      output,
+    }) => {
+      let deal = deal(&deck);
+      simulate(
+        output.as_deref(),
+        (&params).into(),
+        run_options(&run),
+        &deal,
+      )
+      .print();
+    }
+
+    Some(Command::Table {
+      n_min,
+      n_max,
+      k_min,
+      k_max,
+      war_size,
+      honor_threshold,
+      games,
+      exact_max_n,
+      seed,
+      threads,
+    }) => small_games(
+      n_min..=n_max,
+      k_min..=k_max,
+      war_size,
+      honor_threshold,
+      exact_max_n,
+      RunOptions {
+        budget: Budget::Games(games),
+        seed,
+        threads: threads.unwrap_or_else(default_threads),
+      },
+    ),
+
+    Some(Command::Exact {
+      deck,
+      k,
+      war_size,
+      honor_threshold,
+      max_states,
+      games,
+      seed,
+    }) => exact(
+      &deal(&deck),
+      k,
+      war_size,
+      honor_threshold,
+      max_states,
+      games,
+      seed,
+    ),
+
+    Some(Command::HeadToHead {
+      strategies,
+      deck,
+      params: args,
+      run,
+    }) => {
+      let params = Params::from(&args)
+        .with_refill(Refill::Keep)
+        .with_cycle_detection(true)
+        .with_max_turns(args.max_turns.or(Some(20_000)));
+      head_to_head(&deal(&deck), params, &strategies, run_options(&run));
+    }
+
+    Some(Command::Handicap {
+      player,
+      rule,
+      min,
+      max,
+      deck,
+      params,
+      run,
+    }) => {
+      let deal = deal(&deck);
+      if player > deal.players() {
+        eprintln!("error: there is no player {player}");
+        std::process::exit(1);
+      }
+      if matches!(rule, HandicapRule::HonorThreshold) && max > usize::from(u8::MAX) {
+        eprintln!("error: the honor threshold is at most {}", u8::MAX);
+        std::process::exit(1);
+      }
+      let player = Player(player - 1);
+      handicap(
+        &deal,
+        (&params).into(),
+        player,
+        rule,
+        min..=max,
+        run_options(&run),
+      );
+    }
+
+    Some(Command::Search {
+      goal,
+      steps,
+      step_games,
+      deck,
+      params: args,
+      run,
+    }) => {
+      let deal = deal(&deck);
+      if deal.players() != 2 {
+        eprintln!("error: a search splits the deck between exactly two players");
+        std::process::exit(1);
+      }
+      let params = Params::from(&args).with_max_turns(args.max_turns.or(Some(20_000)));
+      search(&deal, params, goal, steps, step_games, run_options(&run));
+    }
+
+    Some(Command::Features {
+      deck,
+      params,
+      rank_sum_bin,
+      games,
+      seed,
+      threads,
+      output,
+    }) => features(
+      &deal(&deck),
+      (&params).into(),
+      rank_sum_bin,
+      RunOptions {
+        budget: Budget::Games(games),
+        seed,
+        threads: threads.unwrap_or_else(default_threads),
+      },
+      output.as_deref(),
+    ),
+
+    Some(Command::Compare {
+      deck,
+      k,
+      honor_threshold,
+      honor_rule,
+      run,
+    }) => compare(
+      &deal(&deck),
+      &k,
+      &honor_threshold,
+      &honor_rule,
+      run_options(&run),
+    ),
+
+    Some(Command::Run {
+      files,
+      seed,
+      threads,
+    }) => {
+      let threads = threads.unwrap_or_else(default_threads);
+
+      for (i, path) in files.iter().enumerate() {
+        let suite = Suite::load(path).unwrap_or_else(|err| {
+          eprintln!("error: {err}");
+          std::process::exit(1);
+        });
+
+        if i > 0 {
+          println!();
+        }
+        run_suite(&suite, seed, threads);
+      }
+    }
+
+    Some(Command::Plot {
+      files,
+      label,
+      output,
+      plot,
+    }) => plot_files(&files, &label, &output, &(&plot).into()),
+
+    Some(Command::Replay {
+      deck,
+      params,
+      seed,
+      trace,
+    }) => replay(&deal(&deck), (&params).into(), seed, trace.as_deref()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn replays_match_the_recorded_games() {
+    // Small decks whose discards are never shuffled often cycle, on different turns with bare ranks
+    // and with suited cards
+    let deal = Deal::Shuffled(card::shoe(1, 3, 4, 0), 2);
+    let params = Params::default()
+      .with_refill(Refill::Keep)
+      .with_cycle_detection(true);
+    let options = RunOptions {
+      budget: Budget::Games(1000),
+      seed: Some(5),
+      threads: 1,
+    };
+    let path = std::env::temp_dir().join(format!("war-replay-{}.jsonl", std::process::id()));
+    simulate(Some(&path), params.clone(), options, &deal);
+
+    let records = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut cycles = 0;
+    for line in records.lines() {
+      let record: serde_json::Value = serde_json::from_str(line).unwrap();
+      let seed = record["seed"].as_u64().unwrap();
+      let (result, turns) = replay_game(&deal, &params, seed, None);
+
+      assert_eq!(record["result"], result.to_string(), "seed {seed}");
+      assert_eq!(record["turns"], turns, "seed {seed}");
+      cycles += usize::from(result == GameResult::Cycle);
+    }
+    assert!(cycles > 0);
+  }
+
+  #[test]
+  fn games_are_added_until_the_precision_targets_are_met() {
+    let deal = Deal::Shuffled(card::shoe(1, 13, 4, 0), 2);
+    let run = |max_games| {
+      let budget = Budget::new(None, None, Some(2.0), Some(20.0), Some(100), max_games).unwrap();
+      let options = RunOptions {
+        budget,
+        seed: Some(1),
+        threads: 1,
+      };
+      simulate(None, Params::default(), options, &deal)
+    };
+
+    let summary = run(None);
+    assert_eq!(summary.precision_met, Some(true));
+    assert!(summary.score.half_width() <= 0.02);
+    assert!(summary.turns.half_width() <= 20.0);
+
+    // Games stop at the limit, even if the targets are not met
+    let summary = run(Some(500));
+    assert_eq!(summary.precision_met, Some(false));
+    assert_eq!(summary.n_games, 500);
+  }
 }
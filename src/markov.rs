@@ -0,0 +1,484 @@
+use crate::scenario::Deal;
+use crate::sim::WarSize;
+use std::collections::HashMap;
+
+/// The number of each rank in every pile of the game: player 1's deck and discard, followed by
+/// player 2's deck and discard.
+///
+/// When discards are shuffled on refill, this is all that matters about the state of a game:
+/// the order of a deck is uniformly random, and the order of a discard is irrelevant since it will
+/// be shuffled before it is drawn from.
+type State = Box<[u8]>;
+
+/// The maximum number of Gauss-Seidel sweeps before giving up on a solution.
+const MAX_ITERATIONS: usize = 1_000_000;
+
+/// How a round ended: in a new state, or with the end of the game.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Outcome {
+  State(usize),
+  Player1,
+  Player2,
+  Draw,
+}
+
+/// The exact win probabilities and expected length of a game.
+pub struct Solution {
+  /// The number of reachable states of the Markov chain
+  pub n_states: usize,
+  pub player1: f64,
+  pub player2: f64,
+  pub draw: f64,
+  pub mean_turns: f64,
+}
+
+impl Solution {
+  /// The mean score of player 1, counting draws as half a win.
+  pub fn mean_score(&self) -> f64 {
+    self.player1 + 0.5 * self.draw
+  }
+}
+
+/// Builds the Markov chain over the states of a game under the shuffle-on-refill rule, and solves it
+/// for the exact probability that each player wins and the expected number of turns.
+pub struct Solver {
+  /// The distinct ranks of the deck, in increasing order. States count cards by their index in this list
+  ranks: Vec<u8>,
+  k: usize,
+  war_size: WarSize,
+  honor_threshold: u8,
+}
+
+impl Solver {
+  /// A solver for games of the deck of `deal`. Returns an error if the deck has more copies of a
+  /// rank than a state can count.
+  pub fn new(
+    deal: &Deal,
+    k: usize,
+    war_size: WarSize,
+    honor_threshold: u8,
+  ) -> Result<Self, String> {
+    let mut ranks = deal.ranks();
+    ranks.sort_unstable();
+    let copies = ranks.chunk_by(|a, b| a == b).map(<[u8]>::len).max();
+    if copies.is_some_and(|copies| copies > usize::from(u8::MAX)) {
+      return Err(format!(
+        "only decks with at most {} copies of each rank can be solved exactly",
+        u8::MAX
+      ));
+    }
+    ranks.dedup();
+
+    Ok(Self {
+      ranks,
+      k,
+      war_size,
+      honor_threshold,
+    })
+  }
+
+  /// Solves the game from the given deal. Returns an error if the chain has more than `max_states` states.
+  pub fn solve(&self, deal: &Deal, max_states: usize, tolerance: f64) -> Result<Solution, String> {
+    if deal.players() != 2 {
+      return Err("only two-player games can be solved exactly".to_string());
+    }
+
+    let initial = self.initial_states(deal, max_states)?;
+
+    // Explore every reachable state, recording the outcomes of a single round from each
+    let mut index = HashMap::new();
+    let mut states = Vec::new();
+    let mut transitions: Vec<Vec<(Outcome, f64)>> = Vec::new();
+
+    let mut intern = |state: State, states: &mut Vec<State>| {
+      *index.entry(state.clone()).or_insert_with(|| {
+        states.push(state);
+        states.len() - 1
+      })
+    };
+
+    let initial: Vec<_> = initial
+      .into_iter()
+      .map(|(state, p)| (intern(state, &mut states), p))
+      .collect();
+
+    while transitions.len() < states.len() {
+      if states.len() > max_states {
+        return Err(too_many_states(max_states));
+      }
+
+      let mut outcomes = HashMap::new();
+      let state = states[transitions.len()].clone();
+      self.flip(state, vec![0; self.ranks.len()], 0, 1.0, &mut outcomes);
+
+      let outcomes = outcomes
+        .into_iter()
+        .map(|(outcome, p)| match outcome {
+          RoundEnd::State(state) => (Outcome::State(intern(state, &mut states)), p),
+          RoundEnd::Player1 => (Outcome::Player1, p),
+          RoundEnd::Player2 => (Outcome::Player2, p),
+          RoundEnd::Draw => (Outcome::Draw, p),
+        })
+        .collect();
+      transitions.push(outcomes);
+    }
+
+    // Solve for the absorption probabilities and expected number of turns from every state, together,
+    // by Gauss-Seidel iteration of `x(s) = sum_t P(s, t) (c(t) + x(t))`
+    let mut x = vec![[0.0; 4]; states.len()];
+
+    for iteration in 0.. {
+      if iteration == MAX_ITERATIONS {
+        return Err(format!(
+          "the solution did not converge after {MAX_ITERATIONS} iterations"
+        ));
+      }
+
+      let mut change = 0f64;
+
+      for (s, outcomes) in transitions.iter().enumerate() {
+        // Player 1 wins, player 2 wins, draws, and number of turns
+        let mut value = [0.0; 4];
+        let mut stay = 0.0;
+
+        for &(outcome, p) in outcomes {
+          value[3] += p;
+          match outcome {
+            Outcome::State(t) if t == s => stay += p,
+            Outcome::State(t) => (0..4).for_each(|i| value[i] += p * x[t][i]),
+            Outcome::Player1 => value[0] += p,
+            Outcome::Player2 => value[1] += p,
+            Outcome::Draw => value[2] += p,
+          }
+        }
+
+        for i in 0..4 {
+          let value = value[i] / (1.0 - stay);
+          change = change.max((value - x[s][i]).abs() / value.abs().max(1.0));
+          x[s][i] = value;
+        }
+      }
+
+      if change <= tolerance {
+        break;
+      }
+    }
+
+    let [player1, player2, draw, mean_turns] = initial.iter().fold([0.0; 4], |mut sum, &(s, p)| {
+      (0..4).for_each(|i| sum[i] += p * x[s][i]);
+      sum
+    });
+
+    Ok(Solution {
+      n_states: states.len(),
+      player1,
+      player2,
+      draw,
+      mean_turns,
+    })
+  }
+
+  /// The distribution of initial states of a deal, with every player's cards in their discard.
+  /// Returns an error as soon as there are more than `max_states` of them, since a shuffled deal of
+  /// a large deck has too many to enumerate.
+  fn initial_states(&self, deal: &Deal, max_states: usize) -> Result<Vec<(State, f64)>, String> {
+    let counts = |cards: &[u8]| -> Vec<u8> {
+      let mut counts = vec![0; self.ranks.len()];
+      for card in cards {
+        counts[self.rank_index(*card)] += 1;
+      }
+      counts
+    };
+
+    let state = |player1: &[u8], player2: &[u8]| -> State {
+      let n = self.ranks.len();
+      let mut state = vec![0; 4 * n];
+      state[n..2 * n].copy_from_slice(player1);
+      state[3 * n..].copy_from_slice(player2);
+      state.into()
+    };
+
+    match deal {
+      Deal::Split(..) | Deal::Fixed(..) => {
+        let hands = deal.hands::<u8>().unwrap();
+        Ok(vec![(state(&counts(&hands[0]), &counts(&hands[1])), 1.0)])
+      }
+
+      // Every way of choosing half of the deck for player 1, weighted by the multivariate
+      // hypergeometric distribution. Of an odd deck, player 1 receives the smaller or the larger
+      // half with equal probability
+      Deal::Shuffled(..) => {
+        let deck = deal.ranks();
+        let total = counts(&deck);
+        let hands = match deck.len() % 2 {
+          0 => vec![deck.len() / 2],
+          _ => vec![deck.len() / 2, deck.len() / 2 + 1],
+        };
+
+        let mut splits = Vec::new();
+        let mut player1 = vec![0; total.len()];
+        for &hand in &hands {
+          if !choose_splits(&total, hand, 0, &mut player1, &mut splits, max_states) {
+            return Err(too_many_states(max_states));
+          }
+        }
+
+        let states = splits
+          .into_iter()
+          .map(|player1| {
+            let player2: Vec<_> = total.iter().zip(&player1).map(|(t, c)| t - c).collect();
+            let hand = player1.iter().map(|&c| c as usize).sum();
+            let ways = binomial(deck.len(), hand) * hands.len() as f64;
+            let p = total
+              .iter()
+              .zip(&player1)
+              .map(|(&t, &c)| binomial(t as usize, c as usize))
+              .product::<f64>()
+              / ways;
+            (state(&player1, &player2), p)
+          })
+          .collect();
+        Ok(states)
+      }
+    }
+  }
+
+  fn rank_index(&self, card: u8) -> usize {
+    self.ranks.binary_search(&card).unwrap()
+  }
+
+  /// Each player flips a card, continuing the round from the given state and loot after `wars` wars,
+  /// which has probability `p` of occurring. Every way the round may end is added to `outcomes`.
+  fn flip(
+    &self,
+    state: State,
+    loot: Vec<u8>,
+    wars: usize,
+    p: f64,
+    outcomes: &mut HashMap<RoundEnd, f64>,
+  ) {
+    self.draw(state, 0, p, &mut |state, card1, p1| {
+      self.draw(state, 1, p1, &mut |state, card2, p2| {
+        let (card1, card2) = match (card1, card2) {
+          (None, None) => return add(outcomes, RoundEnd::Draw, p2),
+          (None, Some(_)) => return add(outcomes, RoundEnd::Player2, p2),
+          (Some(_), None) => return add(outcomes, RoundEnd::Player1, p2),
+          (Some(card1), Some(card2)) => (card1, card2),
+        };
+
+        let (rank1, rank2) = (self.ranks[card1], self.ranks[card2]);
+        let mut loot = loot.clone();
+
+        // Honorable war: the losing card may be removed from the game
+        if rank1 != rank2 && rank1.abs_diff(rank2) <= self.honor_threshold {
+          loot[card1.max(card2)] += 1;
+        } else {
+          loot[card1] += 1;
+          loot[card2] += 1;
+        }
+
+        let n = self.ranks.len();
+        let mut state = state;
+        let winner = match rank1.cmp(&rank2) {
+          std::cmp::Ordering::Greater => 0,
+          std::cmp::Ordering::Less => 1,
+          std::cmp::Ordering::Equal => {
+            // The number of face-down cards only depends on the cards each player owns
+            let remaining = [0, 1].map(|player| {
+              let cards = self.cards(&state, player);
+              let n = self.war_size.face_down(self.k, rank1, wars + 1);
+              n.min(cards.saturating_sub(1))
+            });
+            return self.war(state, loot, remaining, wars + 1, p2, outcomes);
+          }
+        };
+
+        let discard = &mut state[(2 * winner + 1) * n..(2 * winner + 2) * n];
+        for (count, won) in discard.iter_mut().zip(&loot) {
+          *count += won;
+        }
+        add(outcomes, RoundEnd::State(state), p2);
+      });
+    });
+  }
+
+  /// A war, where each player in turn plays their `remaining` face-down cards, and the round
+  /// continues with another flip.
+  fn war(
+    &self,
+    state: State,
+    loot: Vec<u8>,
+    remaining: [usize; 2],
+    wars: usize,
+    p: f64,
+    outcomes: &mut HashMap<RoundEnd, f64>,
+  ) {
+    let Some(player) = remaining.iter().position(|&n| n > 0) else {
+      return self.flip(state, loot, wars, p, outcomes);
+    };
+
+    self.draw(state, player, p, &mut |state, card, p| {
+      let mut loot = loot.clone();
+      loot[card.unwrap()] += 1;
+      let mut remaining = remaining;
+      remaining[player] -= 1;
+      self.war(state, loot, remaining, wars, p, outcomes);
+    });
+  }
+
+  /// The number of cards owned by a player.
+  fn cards(&self, state: &[u8], player: usize) -> usize {
+    let n = self.ranks.len();
+    state[2 * player * n..(2 * player + 2) * n]
+      .iter()
+      .map(|&c| c as usize)
+      .sum()
+  }
+
+  /// A player draws a card from the top of their deck, shuffling their discard into a new deck
+  /// first if it is empty. Calls `f` for every possible card (or `None` if the player has no cards),
+  /// with the resulting state and its probability.
+  fn draw(
+    &self,
+    state: State,
+    player: usize,
+    p: f64,
+    f: &mut dyn FnMut(State, Option<usize>, f64),
+  ) {
+    let n = self.ranks.len();
+    let mut state = state;
+    let deck = 2 * player * n..(2 * player + 1) * n;
+    let discard = (2 * player + 1) * n..(2 * player + 2) * n;
+
+    if state[deck.clone()].iter().all(|&c| c == 0) {
+      let cards: Vec<_> = state[discard.clone()].to_vec();
+      state[deck.clone()].copy_from_slice(&cards);
+      state[discard].fill(0);
+    }
+
+    let total: usize = state[deck.clone()].iter().map(|&c| c as usize).sum();
+    if total == 0 {
+      return f(state, None, p);
+    }
+
+    for card in 0..n {
+      let count = state[deck.start + card];
+      if count > 0 {
+        let mut next = state.clone();
+        next[deck.start + card] -= 1;
+        f(next, Some(card), p * count as f64 / total as f64);
+      }
+    }
+  }
+}
+
+/// How a single round ended, before its state has been interned.
+#[derive(PartialEq, Eq, Hash)]
+enum RoundEnd {
+  State(State),
+  Player1,
+  Player2,
+  Draw,
+}
+
+fn add(outcomes: &mut HashMap<RoundEnd, f64>, outcome: RoundEnd, p: f64) {
+  *outcomes.entry(outcome).or_insert(0.0) += p;
+}
+
+fn too_many_states(max_states: usize) -> String {
+  format!("the game has more than {max_states} states")
+}
+
+/// Enumerates every way to choose `remaining` cards from the ranks `rank..` of `total`. Stops and
+/// returns `false` as soon as there are more than `max` of them.
+fn choose_splits(
+  total: &[u8],
+  remaining: usize,
+  rank: usize,
+  chosen: &mut Vec<u8>,
+  splits: &mut Vec<Vec<u8>>,
+  max: usize,
+) -> bool {
+  if rank == total.len() {
+    if remaining == 0 {
+      splits.push(chosen.clone());
+    }
+    return splits.len() <= max;
+  }
+
+  for c in 0..=(total[rank] as usize).min(remaining) {
+    chosen[rank] = c as u8;
+    if !choose_splits(total, remaining - c, rank + 1, chosen, splits, max) {
+      return false;
+    }
+  }
+  chosen[rank] = 0;
+  true
+}
+
+/// The binomial coefficient `n choose k`, as a float.
+fn binomial(n: usize, k: usize) -> f64 {
+  (0..k).map(|i| (n - i) as f64 / (i + 1) as f64).product()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::card;
+
+  fn solve(player1: &[u8], player2: &[u8], k: usize) -> Solution {
+    let deal = Deal::Fixed(vec![card::from_ranks(player1), card::from_ranks(player2)]);
+    Solver::new(&deal, k, WarSize::Fixed, 0)
+      .unwrap()
+      .solve(&deal, 100_000, 1e-12)
+      .unwrap()
+  }
+
+  #[test]
+  fn single_battle() {
+    // Player 2's higher card wins the only battle, and player 1 is found out of cards on the next
+    // turn, which counts towards the length of the game as in `Game::play`
+    let solution = solve(&[1], &[2], 3);
+    assert_eq!(solution.player1, 0.0);
+    assert!((solution.player2 - 1.0).abs() < 1e-9);
+    assert!((solution.mean_turns - 2.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn equal_hands_are_even() {
+    let solution = solve(&[1, 2, 3], &[1, 2, 3], 1);
+    assert!((solution.player1 - solution.player2).abs() < 1e-9);
+    assert!((solution.player1 + solution.player2 + solution.draw - 1.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn odd_shuffled_deals_are_even() {
+    // Either player receives the extra card, with equal probability
+    let deal = Deal::Shuffled(card::from_ranks(&[1, 2, 3, 4, 5]), 2);
+    let solution = Solver::new(&deal, 1, WarSize::Fixed, 0)
+      .unwrap()
+      .solve(&deal, 100_000, 1e-12)
+      .unwrap();
+    assert!((solution.player1 - solution.player2).abs() < 1e-9);
+  }
+
+  #[test]
+  fn large_shuffled_deals_exceed_the_states_budget() {
+    // A shuffled standard deck has millions of initial states alone, which are not enumerated
+    let deal = Deal::Shuffled(card::shoe(1, 13, 4, 0), 2);
+    let solver = Solver::new(&deal, 3, WarSize::Fixed, 0).unwrap();
+    let err = solver.solve(&deal, 1000, 1e-12).err().unwrap();
+    assert_eq!(err, "the game has more than 1000 states");
+  }
+
+  #[test]
+  fn decks_with_too_many_copies_of_a_rank_are_rejected() {
+    let deal = |copies| Deal::Shuffled(card::shoe(1, 2, copies, 0), 2);
+    assert!(Solver::new(&deal(255), 1, WarSize::Fixed, 0).is_ok());
+    let err = Solver::new(&deal(256), 1, WarSize::Fixed, 0).err().unwrap();
+    assert_eq!(
+      err,
+      "only decks with at most 255 copies of each rank can be solved exactly"
+    );
+  }
+}
@@ -0,0 +1,251 @@
+use crate::stats::Histogram;
+use std::fmt::Write;
+
+/// The colors of successive series, as used by matplotlib.
+const COLORS: [&str; 10] = [
+  "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+  "#bcbd22", "#17becf",
+];
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 480.0;
+/// The margins around the plot area: top, right, bottom, and left
+const MARGIN: [f64; 4] = [40.0, 20.0, 50.0, 80.0];
+
+/// How the game length histograms are drawn.
+pub struct PlotOptions {
+  pub title: String,
+  /// The number of turns per bar
+  pub bin_width: u64,
+  /// The largest number of turns shown; defaults to the largest 99th percentile of the series
+  pub max_turns: Option<u64>,
+}
+
+/// Renders the game length histograms of several scenarios as overlapping, normalized histograms
+/// in an SVG image, in the style of matplotlib's `hist(density=True)`.
+pub fn game_lengths(series: &[(&str, &Histogram)], options: &PlotOptions) -> String {
+  let bin_width = options.bin_width.max(1);
+  let max_turns = options.max_turns.unwrap_or_else(|| {
+    let p99 = series.iter().map(|(_, h)| h.quantile(0.99)).max();
+    let p99 = p99.unwrap_or(0).max(1) as f64;
+    let step = tick_step(p99);
+    ((p99 / step).ceil() * step) as u64
+  });
+
+  let densities: Vec<_> = series
+    .iter()
+    .map(|(_, histogram)| histogram.density(bin_width, max_turns))
+    .collect();
+  let max_density = densities.iter().flatten().copied().fold(0.0, f64::max);
+  let y_max = nice_ceil(if max_density > 0.0 { max_density } else { 1.0 });
+
+  let [top, right, bottom, left] = MARGIN;
+  let (plot_width, plot_height) = (WIDTH - left - right, HEIGHT - top - bottom);
+  let x = |turns: f64| left + plot_width * turns / max_turns as f64;
+  let y = |density: f64| top + plot_height * (1.0 - density / y_max);
+
+  let mut svg = String::new();
+  writeln!(
+    svg,
+    r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif" font-size="12">"#
+  )
+  .unwrap();
+  writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#).unwrap();
+
+  // Horizontal grid lines, with the y ticks
+  let y_step = tick_step(y_max);
+  for i in 0..=(y_max / y_step).round() as usize {
+    let value = i as f64 * y_step;
+    let py = y(value);
+    writeln!(
+      svg,
+      r##"<line x1="{left}" y1="{py:.1}" x2="{:.1}" y2="{py:.1}" stroke="#b0b0b0" stroke-opacity="0.75"/>"##,
+      left + plot_width
+    )
+    .unwrap();
+    writeln!(
+      svg,
+      r#"<text x="{:.1}" y="{py:.1}" text-anchor="end" dominant-baseline="middle">{}</text>"#,
+      left - 6.0,
+      format_tick(value, y_step)
+    )
+    .unwrap();
+  }
+
+  // Bars, as one filled step outline per series
+  for (i, density) in densities.iter().enumerate() {
+    let color = COLORS[i % COLORS.len()];
+    let mut path = format!("M{:.1},{:.1}", x(0.0), y(0.0));
+    for (bin, &value) in density.iter().enumerate() {
+      let start = (bin as u64 * bin_width) as f64;
+      let end = (((bin + 1) as u64 * bin_width).min(max_turns)) as f64;
+      write!(
+        path,
+        " L{:.1},{:.1} L{:.1},{:.1}",
+        x(start),
+        y(value),
+        x(end),
+        y(value)
+      )
+      .unwrap();
+    }
+    write!(path, " L{:.1},{:.1} Z", x(max_turns as f64), y(0.0)).unwrap();
+
+    writeln!(
+      svg,
+      r#"<path d="{path}" fill="{color}" fill-opacity="0.5" stroke="{color}" stroke-width="0.8"/>"#
+    )
+    .unwrap();
+  }
+
+  // Axes, with the x ticks
+  writeln!(
+    svg,
+    r#"<rect x="{left}" y="{top}" width="{plot_width}" height="{plot_height}" fill="none" stroke="black"/>"#
+  )
+  .unwrap();
+
+  let x_step = tick_step(max_turns as f64);
+  for i in 0..=(max_turns as f64 / x_step).floor() as usize {
+    let value = i as f64 * x_step;
+    let px = x(value);
+    writeln!(
+      svg,
+      r#"<line x1="{px:.1}" y1="{:.1}" x2="{px:.1}" y2="{:.1}" stroke="black"/>"#,
+      top + plot_height,
+      top + plot_height + 4.0
+    )
+    .unwrap();
+    writeln!(
+      svg,
+      r#"<text x="{px:.1}" y="{:.1}" text-anchor="middle">{}</text>"#,
+      top + plot_height + 18.0,
+      format_tick(value, x_step)
+    )
+    .unwrap();
+  }
+
+  // Labels and legend
+  writeln!(
+    svg,
+    r#"<text x="{:.1}" y="{:.1}" text-anchor="middle" font-size="16">{}</text>"#,
+    left + plot_width / 2.0,
+    top / 2.0 + 5.0,
+    escape(&options.title)
+  )
+  .unwrap();
+  writeln!(
+    svg,
+    r#"<text x="{:.1}" y="{:.1}" text-anchor="middle">Turns</text>"#,
+    left + plot_width / 2.0,
+    HEIGHT - 12.0
+  )
+  .unwrap();
+  writeln!(
+    svg,
+    r#"<text x="16" y="{:.1}" text-anchor="middle" transform="rotate(-90 16 {:.1})">Frequency</text>"#,
+    top + plot_height / 2.0,
+    top + plot_height / 2.0
+  )
+  .unwrap();
+
+  for (i, (label, _)) in series.iter().enumerate() {
+    let color = COLORS[i % COLORS.len()];
+    let ly = top + 16.0 + 20.0 * i as f64;
+    let lx = left + plot_width - 12.0;
+    writeln!(
+      svg,
+      r#"<rect x="{:.1}" y="{:.1}" width="20" height="10" fill="{color}" fill-opacity="0.5" stroke="{color}"/>"#,
+      lx - 20.0,
+      ly - 5.0
+    )
+    .unwrap();
+    writeln!(
+      svg,
+      r#"<text x="{:.1}" y="{ly:.1}" text-anchor="end" dominant-baseline="middle">{}</text>"#,
+      lx - 26.0,
+      escape(label)
+    )
+    .unwrap();
+  }
+
+  svg.push_str("</svg>\n");
+  svg
+}
+
+/// The smallest "nice" number (1, 2, or 5 times a power of ten) at least `x`.
+fn nice_ceil(x: f64) -> f64 {
+  let magnitude = 10f64.powf(x.log10().floor());
+  [1.0, 2.0, 5.0, 10.0]
+    .into_iter()
+    .map(|m| m * magnitude)
+    .find(|&nice| nice >= x * (1.0 - 1e-9))
+    .unwrap_or(10.0 * magnitude)
+}
+
+/// A nice spacing between ticks on an axis from 0 to `max`, giving about 5 to 10 ticks.
+fn tick_step(max: f64) -> f64 {
+  let step = nice_ceil(max / 10.0);
+  if max / step < 4.0 {
+    step / 2.0
+  } else {
+    step
+  }
+}
+
+/// Formats a tick label with as many decimals as its step needs.
+fn format_tick(value: f64, step: f64) -> String {
+  let decimals = (-step.log10().floor()).max(0.0) as usize;
+  format!("{value:.decimals$}")
+}
+
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn game_lengths_are_drawn_as_steps() {
+    let histogram = Histogram::new([1, 2, 3, 4]);
+    let options = PlotOptions {
+      title: "Short & <sweet>".to_string(),
+      bin_width: 2,
+      max_turns: Some(5),
+    };
+    let svg = game_lengths(&[("a", &histogram), ("b", &histogram)], &options);
+    assert!(svg.starts_with("<svg ") && svg.ends_with("</svg>\n"));
+    assert!(svg.contains(">Short &amp; &lt;sweet&gt;</text>"));
+    assert_eq!(svg.matches("<path ").count(), 2);
+
+    // Densities of 1/8, 1/4, and 1/4 (over a last bin of one turn) on a y axis up to 1/2, and an x
+    // axis up to 5 turns
+    let steps = "M80.0,430.0 L80.0,332.5 L360.0,332.5 L360.0,235.0 L640.0,235.0 L640.0,235.0 \
+                 L780.0,235.0 L780.0,430.0 Z";
+    assert!(svg.contains(steps), "{svg}");
+
+    // Without a maximum, the x axis ends at a tick past the 99th percentile
+    let options = PlotOptions {
+      max_turns: None,
+      ..options
+    };
+    let svg = game_lengths(&[("a", &histogram)], &options);
+    assert!(svg.contains(">4.0</text>") && !svg.contains(">4.5</text>"));
+  }
+
+  #[test]
+  fn ticks_are_nice_numbers() {
+    assert_eq!(nice_ceil(0.3), 0.5);
+    assert_eq!(nice_ceil(7.0), 10.0);
+    assert_eq!(nice_ceil(200.0), 200.0);
+    assert_eq!(tick_step(1000.0), 100.0);
+    assert_eq!(tick_step(30.0), 5.0);
+    assert_eq!(format_tick(0.25, 0.05), "0.25");
+    assert_eq!(format_tick(20.0, 5.0), "20");
+  }
+}
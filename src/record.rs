@@ -0,0 +1,306 @@
+use crate::sim::{Event, GameResult, Observer};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// A summary of a single game, as written by `simulate --output`.
+#[derive(Clone, Serialize)]
+pub struct GameRecord {
+  /// The seed of the game, from which it can be replayed
+  pub seed: u64,
+  pub result: GameResult,
+  pub turns: u64,
+  /// The number of wars, counting each war of a round separately
+  pub wars: u64,
+  /// The number of rounds with at least two wars in a row
+  pub double_wars: u64,
+  /// The number of rounds with at least three wars in a row
+  pub triple_wars: u64,
+  /// The number of cards removed from the game by the honor rule
+  pub honor_removals: u64,
+  /// The number of times a player's discard was shuffled to become their deck
+  pub reshuffles: u64,
+  /// The turn on which each player was eliminated, or `None` for the players left at the end
+  pub eliminations: Vec<Option<u64>>,
+}
+
+impl GameRecord {
+  /// The finishing place of each player, from 1. Players eliminated on the same turn share a place,
+  /// as do the players left in an unfinished game.
+  pub fn places(&self) -> Vec<usize> {
+    let later = |a: Option<u64>, b: Option<u64>| match (a, b) {
+      (None, b) => b.is_some(),
+      (Some(_), None) => false,
+      (Some(a), Some(b)) => a > b,
+    };
+
+    self
+      .eliminations
+      .iter()
+      .map(|&turn| {
+        let ahead = self
+          .eliminations
+          .iter()
+          .filter(|&&other| later(other, turn));
+        1 + ahead.count()
+      })
+      .collect()
+  }
+}
+
+/// The fields of a `GameRecord`, as the header of a CSV file, followed by one column of eliminations
+/// per player.
+const CSV_HEADER: &str = "seed,result,turns,wars,double_wars,triple_wars,honor_removals,reshuffles";
+
+/// An observer that counts the events of a game, building its `GameRecord`.
+pub struct Recorder {
+  record: GameRecord,
+  /// The number of wars so far in the current round
+  round_wars: u64,
+}
+
+impl Recorder {
+  pub fn new(seed: u64) -> Self {
+    Self {
+      record: GameRecord {
+        seed,
+        result: GameResult::Draw,
+        turns: 0,
+        wars: 0,
+        double_wars: 0,
+        triple_wars: 0,
+        honor_removals: 0,
+        reshuffles: 0,
+        eliminations: Vec::new(),
+      },
+      round_wars: 0,
+    }
+  }
+
+  /// The record of the game, once it is over.
+  pub fn into_record(self, eliminations: &[Option<u64>]) -> GameRecord {
+    GameRecord {
+      eliminations: eliminations.to_vec(),
+      ..self.record
+    }
+  }
+}
+
+impl<C> Observer<C> for Recorder {
+  fn observe(&mut self, event: &Event<C>) {
+    let record = &mut self.record;
+    match *event {
+      Event::Round { .. } => self.round_wars = 0,
+      Event::War { .. } => {
+        record.wars += 1;
+        self.round_wars += 1;
+        match self.round_wars {
+          2 => record.double_wars += 1,
+          3 => record.triple_wars += 1,
+          _ => {}
+        }
+      }
+      Event::HonorRemoval { .. } => record.honor_removals += 1,
+      Event::Reshuffle { .. } => record.reshuffles += 1,
+      Event::GameEnd { result, turns } => {
+        record.result = result;
+        record.turns = turns;
+      }
+      Event::Flip { .. }
+      | Event::FaceDown { .. }
+      | Event::Elimination { .. }
+      | Event::RoundWin { .. } => {}
+    }
+  }
+}
+
+/// The only field of a record needed to plot it.
+#[derive(Deserialize)]
+struct Length {
+  turns: u64,
+}
+
+/// Whether a path names a CSV file, rather than a JSON Lines file.
+fn is_csv(path: &Path) -> bool {
+  path.extension().is_some_and(|ext| ext == "csv")
+}
+
+/// Writes one record per game, as CSV if the path has a `.csv` extension and as JSON Lines otherwise.
+pub fn write_records(path: &Path, records: &[GameRecord]) -> std::io::Result<()> {
+  let mut writer = BufWriter::new(File::create(path)?);
+
+  if is_csv(path) {
+    let players = records
+      .first()
+      .map_or(0, |record| record.eliminations.len());
+    write!(writer, "{CSV_HEADER}")?;
+    for i in 1..=players {
+      write!(writer, ",player{i}_eliminated")?;
+    }
+    writeln!(writer)?;
+
+    for record in records {
+      write!(
+        writer,
+        "{},{},{},{},{},{},{},{}",
+        record.seed,
+        record.result,
+        record.turns,
+        record.wars,
+        record.double_wars,
+        record.triple_wars,
+        record.honor_removals,
+        record.reshuffles
+      )?;
+
+      for turn in &record.eliminations {
+        match turn {
+          Some(turn) => write!(writer, ",{turn}")?,
+          None => write!(writer, ",")?,
+        }
+      }
+      writeln!(writer)?;
+    }
+  } else {
+    for record in records {
+      serde_json::to_writer(&mut writer, record)?;
+      writeln!(writer)?;
+    }
+  }
+
+  writer.flush()
+}
+
+/// Reads the game lengths of a file written by `write_records`.
+pub fn read_lengths(path: &Path) -> Result<Vec<u64>, String> {
+  let file = File::open(path).map_err(|err| err.to_string())?;
+  let mut lines = BufReader::new(file).lines();
+  let mut lengths = Vec::new();
+
+  if is_csv(path) {
+    let header = lines.next().transpose().map_err(|err| err.to_string())?;
+    let column = header
+      .and_then(|header| header.split(',').position(|field| field == "turns"))
+      .ok_or("no `turns` column")?;
+
+    for (i, line) in lines.enumerate() {
+      let line = line.map_err(|err| err.to_string())?;
+      let turns = line.split(',').nth(column).and_then(|t| t.parse().ok());
+      lengths.push(turns.ok_or(format!("line {}: invalid `turns`", i + 2))?);
+    }
+  } else {
+    for (i, line) in lines.enumerate() {
+      let line = line.map_err(|err| err.to_string())?;
+      if line.is_empty() {
+        continue;
+      }
+
+      let length: Length =
+        serde_json::from_str(&line).map_err(|err| format!("line {}: {err}", i + 1))?;
+      lengths.push(length.turns);
+    }
+  }
+
+  Ok(lengths)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::sim::Player;
+
+  fn record(
+    seed: u64,
+    result: GameResult,
+    turns: u64,
+    eliminations: Vec<Option<u64>>,
+  ) -> GameRecord {
+    GameRecord {
+      turns,
+      result,
+      eliminations,
+      ..Recorder::new(seed).record
+    }
+  }
+
+  #[test]
+  fn records_round_trip_through_csv_and_json_lines() {
+    let records = [
+      record(7, GameResult::Win(Player(1)), 12, vec![Some(12), None]),
+      record(8, GameResult::Timeout, 40, vec![None, None]),
+    ];
+
+    for extension in ["csv", "jsonl"] {
+      let name = format!("war-records-{}.{extension}", std::process::id());
+      let path = std::env::temp_dir().join(name);
+      write_records(&path, &records).unwrap();
+      let contents = std::fs::read_to_string(&path).unwrap();
+      let lengths = read_lengths(&path);
+      std::fs::remove_file(&path).unwrap();
+
+      assert_eq!(lengths.unwrap(), [12, 40], "{extension}");
+      let first = contents
+        .lines()
+        .nth(usize::from(extension == "csv"))
+        .unwrap();
+      if extension == "csv" {
+        assert!(contents.starts_with(&format!(
+          "{CSV_HEADER},player1_eliminated,player2_eliminated\n"
+        )));
+        assert_eq!(first, "7,player2,12,0,0,0,0,0,12,");
+      } else {
+        assert!(first.contains(r#""result":"player2""#));
+        assert!(first.contains(r#""eliminations":[12,null]"#));
+      }
+    }
+  }
+
+  #[test]
+  fn recorder_counts_the_wars_of_each_round() {
+    let mut recorder = Recorder::new(0);
+    let players = [Player(0), Player(1)];
+    let war = || Event::War {
+      rank: 3,
+      players: &players,
+    };
+    let events: [Event; 8] = [
+      Event::Round { turn: 1 },
+      war(),
+      war(),
+      war(),
+      Event::Round { turn: 2 },
+      war(),
+      Event::Reshuffle {
+        player: Player(0),
+        cards: 5,
+      },
+      Event::GameEnd {
+        result: GameResult::Draw,
+        turns: 2,
+      },
+    ];
+    for event in &events {
+      recorder.observe(event);
+    }
+
+    let record = recorder.into_record(&[Some(2), Some(2)]);
+    assert_eq!(
+      (record.wars, record.double_wars, record.triple_wars),
+      (4, 1, 1)
+    );
+    assert_eq!((record.reshuffles, record.turns), (1, 2));
+    assert_eq!(record.places(), [1, 1]);
+  }
+
+  #[test]
+  fn players_eliminated_later_place_higher() {
+    let record = record(
+      0,
+      GameResult::Win(Player(1)),
+      9,
+      vec![Some(3), None, Some(3), Some(1)],
+    );
+    assert_eq!(record.places(), [2, 1, 2, 4]);
+  }
+}
@@ -0,0 +1,321 @@
+use crate::card::Ranked;
+use crate::cli::RunArgs;
+use crate::record::{GameRecord, Recorder};
+use crate::sim::{Game, GameResult, PlayerDeck, Ruleset};
+use fastrand::Rng;
+use std::ops::Range;
+use std::thread;
+use std::time::Duration;
+
+/// How many games a run plays.
+#[derive(Clone, Copy)]
+pub enum Budget {
+  /// Exactly this many games
+  Games(usize),
+  /// Games are added until at least this much time has elapsed
+  Time(Duration),
+  /// Games are added until the estimates are precise enough
+  Precision(Precision),
+}
+
+impl Default for Budget {
+  fn default() -> Self {
+    Self::Time(Duration::from_secs(1))
+  }
+}
+
+/// A stopping rule based on the width of the 95% confidence intervals of the estimates.
+#[derive(Clone, Copy)]
+pub struct Precision {
+  /// The target half-width of the confidence interval of player 1's mean score, as a fraction
+  pub score: Option<f64>,
+  /// The target half-width of the confidence interval of the mean number of turns
+  pub turns: Option<f64>,
+  /// At least this many games are played, even if the targets are met sooner
+  pub min_games: usize,
+  /// At most this many games are played, even if the targets are not met
+  pub max_games: Option<usize>,
+  /// Games stop being added after this much time, even if the targets are not met
+  pub time: Option<Duration>,
+}
+
+impl Budget {
+  /// The budget given by the options shared by the command line and scenario files. A precision
+  /// target takes priority over a number of games, and the time is then only a limit.
+  /// `score_ci` is given in percentage points. Returns an error if a target can never be met, or
+  /// the limits contradict each other.
+  pub fn new(
+    games: Option<usize>,
+    time: Option<f64>,
+    score_ci: Option<f64>,
+    turns_ci: Option<f64>,
+    min_games: Option<usize>,
+    max_games: Option<usize>,
+  ) -> Result<Self, String> {
+    let positive = |value: Option<f64>| value.is_none_or(|value| value > 0.0 && value.is_finite());
+    if !positive(time) {
+      return Err("the time must be a positive number of seconds".to_string());
+    }
+    if !positive(score_ci) || !positive(turns_ci) {
+      return Err("the precision targets must be positive numbers".to_string());
+    }
+    if [games, min_games, max_games].contains(&Some(0)) {
+      return Err("the numbers of games must be at least 1".to_string());
+    }
+    if let (Some(min_games), Some(max_games)) = (min_games, max_games) {
+      if min_games > max_games {
+        return Err(format!(
+          "the minimum number of games ({min_games}) is more than the maximum ({max_games})"
+        ));
+      }
+    }
+
+    if score_ci.is_some() || turns_ci.is_some() {
+      // The default minimum number of games gives way to a smaller maximum
+      let min_games = min_games
+        .unwrap_or(1000)
+        .min(max_games.unwrap_or(usize::MAX));
+      return Ok(Self::Precision(Precision {
+        score: score_ci.map(|score| score / 100.0),
+        turns: turns_ci,
+        min_games,
+        max_games,
+        time: time.map(Duration::from_secs_f64),
+      }));
+    }
+
+    Ok(match (games, time) {
+      (Some(n_games), _) => Self::Games(n_games),
+      (None, Some(time)) => Self::Time(Duration::from_secs_f64(time)),
+      (None, None) => Self::default(),
+    })
+  }
+}
+
+/// How a batch of games is run: how many, from which seed, and on how many threads.
+#[derive(Clone, Copy)]
+pub struct RunOptions {
+  pub budget: Budget,
+  /// The master seed, or `None` for a random one
+  pub seed: Option<u64>,
+  pub threads: usize,
+}
+
+impl Default for RunOptions {
+  fn default() -> Self {
+    Self {
+      budget: Budget::default(),
+      seed: None,
+      threads: default_threads(),
+    }
+  }
+}
+
+impl TryFrom<&RunArgs> for RunOptions {
+  type Error = String;
+
+  fn try_from(args: &RunArgs) -> Result<Self, String> {
+    let budget = Budget::new(
+      args.games,
+      args.time,
+      args.score_ci,
+      args.turns_ci,
+      args.min_games,
+      args.max_games,
+    )?;
+
+    Ok(Self {
+      budget,
+      seed: args.seed,
+      threads: args.threads.unwrap_or_else(default_threads),
+    })
+  }
+}
+
+/// The number of threads to use when none is given: one per available core.
+pub fn default_threads() -> usize {
+  thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Returns the given master seed, or a random one if none is given.
+pub fn master_seed(seed: Option<u64>) -> u64 {
+  seed.unwrap_or_else(|| fastrand::u64(..))
+}
+
+/// Derives the seed of the `index`th game of a run from the run's master seed (using SplitMix64),
+/// so that any game can be replayed on its own, independently of the games before it.
+pub fn game_seed(master: u64, index: u64) -> u64 {
+  let mut z = master.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+  z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  z ^ (z >> 31)
+}
+
+/// Deals a single game from its seed. The same random number generator deals the initial decks
+/// and then plays the game, so the seed alone determines the entire game.
+pub fn new_game<C, R, F>(rules: &R, seed: u64, f: &F) -> Game<C, R>
+where
+  C: Ranked,
+  R: Ruleset<C>,
+  F: Fn(&mut Rng) -> Vec<PlayerDeck<C>>,
+{
+  let mut rng = Rng::with_seed(seed);
+  let players = f(&mut rng);
+  Game::new(rules.clone(), rng, players)
+}
+
+/// Plays the games with the given indices of a run, split into contiguous chunks across `threads`
+/// worker threads. Since every game is seeded from its index, the results (in index order) do not
+/// depend on the number of threads.
+pub fn play_games<C, R, F>(
+  rules: &R,
+  seed: u64,
+  indices: Range<usize>,
+  threads: usize,
+  f: &F,
+) -> Vec<(GameResult, u64)>
+where
+  C: Ranked,
+  R: Ruleset<C> + Sync,
+  F: Fn(&mut Rng) -> Vec<PlayerDeck<C>> + Sync,
+{
+  par_map(indices, threads, |i| {
+    new_game(rules, game_seed(seed, i as u64), f).play()
+  })
+}
+
+/// Plays games like `play_games`, counting the events of each game into its record.
+pub fn record_games<C, R, F>(
+  rules: &R,
+  seed: u64,
+  indices: Range<usize>,
+  threads: usize,
+  f: &F,
+) -> Vec<GameRecord>
+where
+  C: Ranked,
+  R: Ruleset<C> + Sync,
+  F: Fn(&mut Rng) -> Vec<PlayerDeck<C>> + Sync,
+{
+  par_map(indices, threads, |i| {
+    let seed = game_seed(seed, i as u64);
+    let mut recorder = Recorder::new(seed);
+    let mut game = new_game(rules, seed, f);
+    game.play_observed(&mut recorder);
+    recorder.into_record(game.eliminations())
+  })
+}
+
+/// Maps every index to a value, split into contiguous chunks across `threads` worker threads,
+/// and returns the values in index order.
+fn par_map<T, G>(indices: Range<usize>, threads: usize, g: G) -> Vec<T>
+where
+  T: Send,
+  G: Fn(usize) -> T + Sync,
+{
+  let play_chunk = |chunk: Range<usize>| -> Vec<_> { chunk.map(&g).collect() };
+
+  let chunk_size = indices.len().div_ceil(threads.max(1)).max(1);
+  if chunk_size >= indices.len() {
+    return play_chunk(indices);
+  }
+
+  thread::scope(|scope| {
+    let handles: Vec<_> = indices
+      .clone()
+      .step_by(chunk_size)
+      .map(|start| {
+        let chunk = start..(start + chunk_size).min(indices.end);
+        scope.spawn(move || play_chunk(chunk))
+      })
+      .collect();
+
+    handles
+      .into_iter()
+      .flat_map(|handle| handle.join().unwrap())
+      .collect()
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::card;
+  use crate::scenario::Deal;
+  use crate::sim::Params;
+  use std::collections::HashSet;
+
+  /// Deals half of a shuffled standard deck to each of two players.
+  fn deal(rng: &mut Rng) -> Vec<PlayerDeck> {
+    Deal::Shuffled(card::shoe(1, 13, 4, 0), 2).deal(rng)
+  }
+
+  #[test]
+  fn games_replay_from_their_seeds() {
+    let seeds: HashSet<_> = (0..10_000).map(|i| game_seed(42, i)).collect();
+    assert_eq!(seeds.len(), 10_000);
+    assert_ne!(game_seed(42, 0), game_seed(43, 0));
+
+    // A game's seed alone determines it, whatever run it was played in
+    let params = Params::default();
+    let results = play_games(&params, 42, 0..100, 1, &deal);
+    for (i, &result) in results.iter().enumerate() {
+      let replayed = new_game(&params, game_seed(42, i as u64), &deal).play();
+      assert!(replayed == result, "game {i}");
+    }
+    assert!(play_games(&params, 42, 0..100, 1, &deal) == results);
+  }
+
+  #[test]
+  fn results_do_not_depend_on_the_number_of_threads() {
+    let params = Params::new(3, 1);
+    let play = |indices: Range<usize>, threads| play_games(&params, 7, indices, threads, &deal);
+    let record = |threads| {
+      let records = record_games(&params, 7, 0..1000, threads, &deal);
+      serde_json::to_string(&records).unwrap()
+    };
+
+    let single = play(0..1000, 1);
+    for threads in [2, 3, 8] {
+      assert!(play(0..1000, threads) == single, "{threads} threads");
+      assert_eq!(record(threads), record(1), "{threads} threads");
+    }
+
+    // A later batch of a run plays the same games as a single batch would
+    assert!(play(500..1000, 4) == single[500..]);
+  }
+
+  #[test]
+  fn precision_targets_take_priority() {
+    let Ok(Budget::Precision(precision)) =
+      Budget::new(Some(10), Some(2.0), Some(1.0), None, None, None)
+    else {
+      panic!("expected a precision target");
+    };
+    assert_eq!(precision.score, Some(0.01));
+    assert_eq!(precision.min_games, 1000);
+    assert_eq!(precision.time, Some(Duration::from_secs(2)));
+
+    // The default minimum gives way to a smaller maximum
+    let Ok(Budget::Precision(precision)) =
+      Budget::new(None, None, None, Some(0.5), None, Some(100))
+    else {
+      panic!("expected a precision target");
+    };
+    assert_eq!(precision.min_games, 100);
+  }
+
+  #[test]
+  fn budgets_that_cannot_be_met_are_rejected() {
+    let budget = |score_ci, min_games, max_games| {
+      Budget::new(None, None, score_ci, None, min_games, max_games)
+    };
+    for score_ci in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+      assert!(budget(Some(score_ci), None, None).is_err(), "{score_ci}");
+    }
+    assert!(budget(Some(1.0), Some(200), Some(100)).is_err());
+    assert!(budget(Some(1.0), Some(100), Some(100)).is_ok());
+    assert!(budget(None, Some(0), None).is_err());
+    assert!(Budget::new(None, Some(0.0), None, None, None, None).is_err());
+  }
+}
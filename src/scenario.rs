@@ -0,0 +1,473 @@
+use crate::card::{self, Card, Ranked};
+use crate::cli::{parse_cards, Cards, DealMode, DeckArgs, ParamsArgs};
+use crate::plot::PlotOptions;
+use crate::runner::{Budget, RunOptions};
+use crate::sim::{Params, PlayerDeck};
+use fastrand::Rng;
+use serde::{de::Error, Deserialize, Deserializer};
+use std::path::{Path, PathBuf};
+
+/// How a deck is dealt to the players at the start of each game.
+pub enum Deal {
+  /// The deck is shuffled, and split evenly between this many players. If it does not divide
+  /// evenly, the players who receive one more card are chosen at random
+  Shuffled(Vec<Card>, usize),
+  /// Each of this many players receives every n-th card of the sorted deck, and so the same ranks
+  /// as every other player
+  Split(Vec<Card>, usize),
+  /// Each player receives exactly these cards
+  Fixed(Vec<Vec<Card>>),
+}
+
+impl Deal {
+  pub fn from_args(args: &DeckArgs) -> Result<Self, String> {
+    Self::new(
+      args.deal,
+      card::shoe(args.decks, args.ranks, args.copies, args.jokers),
+      args.players,
+      args.player1.as_ref().zip(args.player2.as_ref()),
+    )
+  }
+
+  /// A deal of the deck to `players` players, unless the two players' cards are given explicitly
+  /// as ranks. Returns an error if the deck is split, but the copies of some rank cannot be shared
+  /// evenly between the players, since a seat would then hold better cards than the others.
+  fn new(
+    mode: DealMode,
+    deck: Vec<Card>,
+    players: usize,
+    hands: Option<(&Cards, &Cards)>,
+  ) -> Result<Self, String> {
+    if let Some((player1, player2)) = hands {
+      return Ok(Self::Fixed(vec![
+        card::from_ranks(player1),
+        card::from_ranks(player2),
+      ]));
+    }
+
+    match mode {
+      DealMode::Shuffled => Ok(Self::Shuffled(deck, players)),
+      DealMode::Split => {
+        let mut copies = std::collections::HashMap::new();
+        for card in &deck {
+          *copies.entry(card.rank).or_insert(0) += 1;
+        }
+        if copies.values().any(|&n| n % players != 0) {
+          return Err(format!(
+            "to split the deck, the copies of every rank must divide evenly between the {players} players"
+          ));
+        }
+        Ok(Self::Split(deck, players))
+      }
+    }
+  }
+
+  /// The number of players dealt to.
+  pub fn players(&self) -> usize {
+    match self {
+      Self::Shuffled(_, players) | Self::Split(_, players) => *players,
+      Self::Fixed(hands) => hands.len(),
+    }
+  }
+
+  /// Every card of the deck.
+  pub fn cards(&self) -> Vec<Card> {
+    match self {
+      Self::Shuffled(deck, _) | Self::Split(deck, _) => deck.clone(),
+      Self::Fixed(hands) => hands.concat(),
+    }
+  }
+
+  /// The ranks of every card in the deck.
+  pub fn ranks(&self) -> Vec<u8> {
+    match self {
+      Self::Shuffled(deck, _) | Self::Split(deck, _) => deck.iter().map(|card| card.rank).collect(),
+      Self::Fixed(hands) => hands.iter().flatten().map(|card| card.rank).collect(),
+    }
+  }
+
+  /// Deals the players' initial decks, as `Card`s or as bare ranks. Both shuffle the deck the same
+  /// way, so that a game plays out the same with either.
+  pub fn deal<C: Ranked>(&self, rng: &mut Rng) -> Vec<PlayerDeck<C>> {
+    let hands = self.deal_hands(rng);
+    hands.into_iter().map(PlayerDeck::new).collect()
+  }
+
+  /// Deals the players' initial cards, as `deal` does.
+  pub fn deal_hands<C: Ranked>(&self, rng: &mut Rng) -> Vec<Vec<C>> {
+    match self {
+      // Each player receives a contiguous part of the shuffled deck. If the deck does not divide
+      // evenly, the parts of one more card are rotated to random seats, so that no seat has an edge
+      Self::Shuffled(deck, players) => {
+        let mut deck: Vec<C> = deck.iter().map(|&card| C::from_card(card)).collect();
+        rng.shuffle(&mut deck);
+        let offset = match deck.len() % players {
+          0 => 0,
+          _ => rng.usize(..*players),
+        };
+
+        (0..*players)
+          .map(|seat| {
+            let i = (seat + offset) % players;
+            let range = i * deck.len() / players..(i + 1) * deck.len() / players;
+            deck[range].to_vec()
+          })
+          .collect()
+      }
+
+      Self::Split(..) | Self::Fixed(..) => self.hands().unwrap(),
+    }
+  }
+
+  /// The players' initial cards, if they do not depend on the shuffle.
+  pub fn hands<C: Ranked>(&self) -> Option<Vec<Vec<C>>> {
+    let convert = |cards: &[Card]| cards.iter().map(|&card| C::from_card(card)).collect();
+
+    match self {
+      Self::Shuffled(..) => None,
+
+      Self::Split(deck, players) => {
+        let mut deck = deck.clone();
+        deck.sort_unstable();
+
+        let hands = (0..*players)
+          .map(|i| {
+            let hand: Vec<_> = deck.iter().skip(i).step_by(*players).copied().collect();
+            convert(&hand)
+          })
+          .collect();
+        Some(hands)
+      }
+
+      Self::Fixed(hands) => Some(hands.iter().map(|hand| convert(hand)).collect()),
+    }
+  }
+}
+
+/// A list of scenarios, as loaded from a TOML or JSON file of `[[scenario]]` tables.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Suite {
+  #[serde(rename = "scenario")]
+  pub scenarios: Vec<Scenario>,
+  pub plot: Option<Plot>,
+}
+
+/// A plot of the game length histograms of some of the scenarios of a suite, written once every
+/// scenario has run.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Plot {
+  /// The SVG file to write
+  pub output: PathBuf,
+  /// The names of the plotted scenarios, in legend order
+  pub scenarios: Vec<String>,
+  #[serde(default = "default_title")]
+  title: String,
+  #[serde(default = "default_bin_width")]
+  bin_width: u64,
+  max_turns: Option<u64>,
+}
+
+impl Plot {
+  pub fn options(&self) -> PlotOptions {
+    PlotOptions {
+      title: self.title.clone(),
+      bin_width: self.bin_width,
+      max_turns: self.max_turns,
+    }
+  }
+}
+
+fn default_title() -> String {
+  "Game Lengths".to_string()
+}
+
+fn default_bin_width() -> u64 {
+  10
+}
+
+/// A single game setup: how the deck is dealt, the rules, and how many games to simulate.
+/// Every field but the name is optional, and defaults to the same value as the command-line option.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+  pub name: String,
+
+  #[serde(default = "default_deal")]
+  deal: DealMode,
+  #[serde(default = "default_ranks")]
+  ranks: u8,
+  #[serde(default = "default_copies")]
+  copies: usize,
+  #[serde(default)]
+  jokers: usize,
+  #[serde(default = "default_decks")]
+  decks: usize,
+  #[serde(default = "default_players")]
+  players: usize,
+  /// Explicit initial cards, either as a list of ranks or in the command-line syntax, e.g. `"13x4"`
+  #[serde(default, deserialize_with = "deserialize_cards")]
+  player1: Option<Cards>,
+  #[serde(default, deserialize_with = "deserialize_cards")]
+  player2: Option<Cards>,
+
+  /// The rules, under the same names as the command-line options
+  #[serde(flatten)]
+  params: ParamsArgs,
+
+  /// Exactly this many games are simulated, if given; otherwise, games are simulated for `time` seconds,
+  /// or until the precision targets are met (see `RunArgs`)
+  games: Option<usize>,
+  time: Option<f64>,
+  score_ci: Option<f64>,
+  turns_ci: Option<f64>,
+  min_games: Option<usize>,
+  max_games: Option<usize>,
+  seed: Option<u64>,
+
+  /// Writes a record of every game to this file, as CSV if it ends in `.csv` and as JSON Lines otherwise
+  pub output: Option<PathBuf>,
+}
+
+fn default_deal() -> DealMode {
+  DealMode::Shuffled
+}
+
+fn default_ranks() -> u8 {
+  13
+}
+
+fn default_copies() -> usize {
+  4
+}
+
+fn default_decks() -> usize {
+  1
+}
+
+fn default_players() -> usize {
+  2
+}
+
+/// A list of cards, either given as ranks or in the shorthand accepted by `parse_cards`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CardList {
+  Ranks(Cards),
+  Shorthand(String),
+}
+
+fn deserialize_cards<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Option<Cards>, D::Error> {
+  match Option::<CardList>::deserialize(deserializer)? {
+    None => Ok(None),
+    Some(CardList::Ranks(cards)) => Ok(Some(cards)),
+    Some(CardList::Shorthand(s)) => parse_cards(&s).map(Some).map_err(D::Error::custom),
+  }
+}
+
+impl Suite {
+  /// Loads a suite from a file, parsed as JSON if it has a `.json` extension and as TOML otherwise.
+  pub fn load(path: &Path) -> Result<Self, String> {
+    let contents =
+      std::fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+
+    let suite = if path.extension().is_some_and(|ext| ext == "json") {
+      Self::from_json(&contents)
+    } else {
+      Self::from_toml(&contents)
+    };
+
+    suite.map_err(|err| format!("{}: {err}", path.display()))
+  }
+
+  pub fn from_toml(contents: &str) -> Result<Self, String> {
+    let suite: Self = toml::from_str(contents).map_err(|err| err.to_string())?;
+    suite.validate()
+  }
+
+  pub fn from_json(contents: &str) -> Result<Self, String> {
+    let suite: Self = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+    suite.validate()
+  }
+
+  fn validate(self) -> Result<Self, String> {
+    for scenario in &self.scenarios {
+      if scenario.player1.is_some() != scenario.player2.is_some() {
+        return Err(format!(
+          "scenario `{}`: `player1` and `player2` must be given together",
+          scenario.name
+        ));
+      }
+
+      if scenario.players < 2 || (scenario.player1.is_some() && scenario.players != 2) {
+        return Err(format!(
+          "scenario `{}`: there must be at least two players, and exactly two with `player1` and `player2`",
+          scenario.name
+        ));
+      }
+
+      if let Err(err) = scenario.try_deal().and(scenario.budget()) {
+        return Err(format!("scenario `{}`: {err}", scenario.name));
+      }
+    }
+
+    if let Some(plot) = &self.plot {
+      if plot.bin_width == 0 || plot.max_turns == Some(0) {
+        return Err("plot: `bin_width` and `max_turns` must be at least 1".to_string());
+      }
+
+      for name in &plot.scenarios {
+        if !self.scenarios.iter().any(|scenario| &scenario.name == name) {
+          return Err(format!("plot: no scenario named `{name}`"));
+        }
+      }
+    }
+
+    Ok(self)
+  }
+}
+
+impl Scenario {
+  pub fn deal(&self) -> Deal {
+    // The deal was checked when the suite was loaded
+    self.try_deal().unwrap()
+  }
+
+  fn try_deal(&self) -> Result<Deal, String> {
+    Deal::new(
+      self.deal,
+      card::shoe(self.decks, self.ranks, self.copies, self.jokers),
+      self.players,
+      self.player1.as_ref().zip(self.player2.as_ref()),
+    )
+  }
+
+  pub fn params(&self) -> Params {
+    Params::from(&self.params)
+  }
+
+  /// The budget of games of this scenario.
+  fn budget(&self) -> Result<Budget, String> {
+    Budget::new(
+      self.games,
+      self.time,
+      self.score_ci,
+      self.turns_ci,
+      self.min_games,
+      self.max_games,
+    )
+  }
+
+  /// The run options of this scenario. A master seed given on the command line overrides the scenario's.
+  pub fn run_options(&self, seed: Option<u64>, threads: usize) -> RunOptions {
+    RunOptions {
+      // The budget was checked when the suite was loaded
+      budget: self.budget().unwrap(),
+      seed: seed.or(self.seed),
+      threads,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::strategy::PlayStrategy;
+
+  #[test]
+  fn extra_cards_of_a_shuffled_deal_go_to_random_seats() {
+    // 52 cards are dealt to three players as 17, 17, and 18 cards
+    let deal = Deal::Shuffled(card::shoe(1, 13, 4, 0), 3);
+    let mut larger = [0; 3];
+    for seed in 0..300 {
+      let hands = deal.deal_hands::<u8>(&mut Rng::with_seed(seed));
+      let sizes: Vec<_> = hands.iter().map(Vec::len).collect();
+      assert_eq!(sizes.iter().sum::<usize>(), 52);
+      larger[sizes.iter().position(|&size| size == 18).unwrap()] += 1;
+    }
+    assert!(larger.iter().all(|&n| n > 50), "{larger:?}");
+  }
+
+  #[test]
+  fn split_deals_give_every_seat_the_same_ranks() {
+    let split = |decks, copies, jokers, players| {
+      let deck = card::shoe(decks, 13, copies, jokers);
+      Deal::new(DealMode::Split, deck, players, None)
+    };
+
+    for (decks, copies, jokers, players) in [(1, 4, 0, 2), (1, 4, 0, 4), (2, 3, 1, 2), (1, 6, 3, 3)]
+    {
+      let deal = split(decks, copies, jokers, players).unwrap();
+      let hands = deal.deal_hands::<u8>(&mut Rng::with_seed(0));
+      assert_eq!(hands.len(), players);
+      assert!(hands.iter().all(|hand| hand == &hands[0]), "{hands:?}");
+    }
+
+    // An extra card, or an odd number of copies, would go to the first seats
+    assert!(split(1, 4, 0, 3).is_err());
+    assert!(split(1, 3, 0, 2).is_err());
+    assert!(split(1, 4, 1, 2).is_err());
+  }
+
+  #[test]
+  fn the_bundled_suites_load() {
+    for entry in std::fs::read_dir("scenarios").unwrap() {
+      let path = entry.unwrap().path();
+      assert!(Suite::load(&path).is_ok(), "{}", path.display());
+    }
+  }
+
+  #[test]
+  fn scenarios_take_the_rules_of_the_command_line() {
+    let suite = Suite::from_toml(
+      r#"
+      [[scenario]]
+      name = "honor"
+      k = 1
+      honor_threshold = 2
+      player_k = [1, 3]
+      strategy = ["highest"]
+      "#,
+    )
+    .unwrap();
+    let params = &suite.scenarios[0].params;
+    assert_eq!((params.k, params.honor_threshold), (1, 2));
+    assert_eq!(params.player_k, [1, 3]);
+    assert!(params.strategy == [PlayStrategy::Highest]);
+    assert!(params.max_turns.is_none() && params.loot_strategy.is_empty());
+
+    // Rules not given take the defaults of the command line
+    let suite = Suite::from_json(r#"{"scenario": [{"name": "standard"}]}"#).unwrap();
+    assert_eq!(suite.scenarios[0].params.k, 3);
+  }
+
+  #[test]
+  fn malformed_suites_are_rejected() {
+    let error = |suite: &str| Suite::from_toml(suite).err().unwrap();
+    let scenario = |fields: &str| error(&format!("[[scenario]]\nname = \"a\"\n{fields}"));
+
+    assert!(scenario("kk = 1").contains("unknown field `kk`"));
+    assert!(scenario("player1 = \"13x4\"").contains("must be given together"));
+    assert!(scenario("players = 1").contains("at least two players"));
+    assert!(scenario("deal = \"split\"\nplayers = 3").contains("scenario `a`: to split the deck"));
+    assert!(scenario("score_ci = 0.0").contains("scenario `a`: the precision targets"));
+    assert!(scenario("turns_ci = -1.0").contains("scenario `a`: the precision targets"));
+    assert!(scenario("score_ci = 1.0\nmin_games = 20\nmax_games = 10").contains("minimum"));
+    assert!(scenario("time = 0.0").contains("scenario `a`: the time"));
+
+    let plot = |fields: &str| {
+      error(&format!(
+        "[[scenario]]\nname = \"a\"\n[plot]\noutput = \"a.svg\"\nscenarios = [\"a\"]\n{fields}"
+      ))
+    };
+    assert!(plot("bin_width = 0").contains("plot: `bin_width` and `max_turns`"));
+    assert!(plot("max_turns = 0").contains("plot: `bin_width` and `max_turns`"));
+    assert!(
+      error("[[scenario]]\nname = \"a\"\n[plot]\noutput = \"a.svg\"\nscenarios = [\"b\"]")
+        .contains("no scenario named `b`")
+    );
+  }
+}
@@ -0,0 +1,135 @@
+use crate::card::Card;
+use fastrand::Rng;
+use std::cmp::Reverse;
+
+/// An initial split of a deck between two players: the cards of player 1, then of player 2.
+pub type Split = [Vec<Card>; 2];
+
+/// The split of `deck` in which player 1 holds its `size` highest cards, and player 2 the rest.
+pub fn top(deck: &[Card], size: usize) -> Split {
+  let mut deck = deck.to_vec();
+  deck.sort_unstable_by_key(|&card| Reverse(card));
+  let rest = deck.split_off(size);
+  [deck, rest]
+}
+
+/// A random split of `deck` in which player 1 holds `size` cards, and player 2 the rest.
+pub fn random(deck: &[Card], size: usize, rng: &mut Rng) -> Split {
+  let mut deck = deck.to_vec();
+  rng.shuffle(&mut deck);
+  let rest = deck.split_off(size);
+  [deck, rest]
+}
+
+/// Searches for a split that maximizes `objective` by hill climbing from `start`. Each of `steps`
+/// steps swaps a random card of player 1 for a random card of player 2, and keeps the swap if it
+/// improves the objective; swaps of equal ranks are skipped, since they cannot change the game.
+/// Returns the best split found and its objective.
+pub fn climb(
+  start: Split,
+  steps: usize,
+  rng: &mut Rng,
+  mut objective: impl FnMut(&Split) -> f64,
+) -> (Split, f64) {
+  let mut best = start;
+  let mut value = objective(&best);
+  if best.iter().any(Vec::is_empty) {
+    return (best, value);
+  }
+
+  for _ in 0..steps {
+    let i = rng.usize(..best[0].len());
+    let j = rng.usize(..best[1].len());
+    if best[0][i].rank == best[1][j].rank {
+      continue;
+    }
+
+    let mut candidate = best.clone();
+    let [first, second] = &mut candidate;
+    std::mem::swap(&mut first[i], &mut second[j]);
+
+    let candidate_value = objective(&candidate);
+    if candidate_value > value {
+      best = candidate;
+      value = candidate_value;
+    }
+  }
+
+  (best, value)
+}
+
+/// Searches for the smallest hand of `deck` with which player 1 wins at least half of the games, by
+/// bisection over the hand sizes from 1 to half the deck, assuming that a larger hand never does
+/// worse. At each size tried, the best hand is searched by hill climbing from the highest cards
+/// (see `climb`), for the player 1 `score` of a split. Returns the best split and score at each size
+/// tried, in order; the smallest winning hand is the smallest of them that wins.
+pub fn smallest_winner(
+  deck: &[Card],
+  steps: usize,
+  rng: &mut Rng,
+  mut score: impl FnMut(&Split) -> f64,
+) -> Vec<(Split, f64)> {
+  let mut tried = Vec::new();
+  let mut best = |size: usize, tried: &mut Vec<(Split, f64)>| {
+    let (split, value) = climb(top(deck, size), steps, rng, &mut score);
+    tried.push((split, value));
+    value >= 0.5
+  };
+
+  // Half the deck is only searched once, and a smaller hand is searched for only if it wins
+  let (mut low, mut high) = (1, deck.len() / 2);
+  if !best(high, &mut tried) {
+    return tried;
+  }
+
+  while low < high {
+    let mid = (low + high) / 2;
+    if best(mid, &mut tried) {
+      high = mid;
+    } else {
+      low = mid + 1;
+    }
+  }
+
+  tried
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::card;
+
+  /// The share of the rank total of the deck held by player 1.
+  fn share(split: &Split) -> f64 {
+    let sum = |cards: &[Card]| cards.iter().map(|card| f64::from(card.rank)).sum::<f64>();
+    sum(&split[0]) / (sum(&split[0]) + sum(&split[1]))
+  }
+
+  #[test]
+  fn climbing_finds_the_best_split() {
+    let deck = card::shoe(1, 13, 1, 0);
+    let mut rng = Rng::with_seed(0);
+    let start = random(&deck, 4, &mut rng);
+    let (split, value) = climb(start, 1000, &mut rng, share);
+    assert_eq!(split[0].len(), 4);
+    assert_eq!(value, share(&top(&deck, 4)));
+  }
+
+  #[test]
+  fn the_smallest_winning_hand_is_found_by_bisection() {
+    // The ranks 1 to 13 add up to 91, and the four highest to 46, more than half
+    let deck = card::shoe(1, 13, 1, 0);
+    let tried = smallest_winner(&deck, 100, &mut Rng::with_seed(0), share);
+    let sizes: Vec<_> = tried.iter().map(|(split, _)| split[0].len()).collect();
+    assert_eq!(sizes, [6, 3, 5, 4]);
+
+    let (split, score) = &tried[3];
+    let ranks: Vec<_> = split[0].iter().map(|card| card.rank).collect();
+    assert_eq!(ranks, [13, 12, 11, 10]);
+    assert_eq!(*score, 46.0 / 91.0);
+
+    // Nothing smaller is searched for if half the deck does not win
+    let tried = smallest_winner(&deck, 100, &mut Rng::with_seed(0), |_| 0.0);
+    assert_eq!(tried.len(), 1);
+  }
+}
@@ -1,65 +1,551 @@
+use crate::card::{Ranked, SuitRules, JOKER};
+use crate::cli::ParamsArgs;
+use crate::strategy::{BuiltinStrategy, LootStrategy, PlayStrategy, Strategy};
+use clap::ValueEnum;
 use fastrand::Rng;
-use std::cmp::Ordering;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
 
-pub enum Player {
-  Player1,
-  Player2,
+/// A player, by their seat at the table (0 for player 1). Serialized as `player1`, `player2`, etc.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Player(pub usize);
+
+impl fmt::Display for Player {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "player{}", self.0 + 1)
+  }
+}
+
+impl Serialize for Player {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
 }
 
 /// The winner of a game (repeated rounds, until one player has the entire deck).
-/// The game may draw if both players flip their last card in a war.
+/// The game may draw if every remaining player flips their last card in the same war.
+/// If enabled in the `Params`, a game may also be stopped before it has a winner.
+/// Serialized as the winning player, or `draw`, `timeout`, or `cycle`.
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum GameResult {
-  Player1,
-  Player2,
+  Win(Player),
   Draw,
+  /// The game reached `Params::max_turns` without a winner
+  Timeout,
+  /// The game returned to an earlier state, and so would never end
+  Cycle,
+}
+
+impl fmt::Display for GameResult {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::Win(player) => write!(f, "{player}"),
+      Self::Draw => write!(f, "draw"),
+      Self::Timeout => write!(f, "timeout"),
+      Self::Cycle => write!(f, "cycle"),
+    }
+  }
+}
+
+impl Serialize for GameResult {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
 }
 
 /// The winner of an individual round, which may consist of one or more wars.
 enum RoundResult {
   GameResult(GameResult),
   RoundWin(Player),
+  /// Every player in a war ran out of cards, while more than one other player remains.
+  /// The loot is removed from the game
+  NoWinner,
+}
+
+/// Something that happened during a game, as reported to an `Observer`.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a, C = u8> {
+  /// A new round begins
+  Round { turn: u64 },
+  /// A player's deck ran out, so their discard of `cards` cards is shuffled to become their new deck
+  /// (only if `Refill::Shuffle`)
+  Reshuffle { player: Player, cards: usize },
+  /// Each player still in the round flips a card face-up
+  Flip {
+    players: &'a [Player],
+    cards: &'a [C],
+  },
+  /// The highest flipped cards are equal, so a war begins between the players who flipped them
+  War { rank: u8, players: &'a [Player] },
+  /// A player plays cards face-down in a war
+  FaceDown { player: Player, cards: &'a [C] },
+  /// A card is removed from the game, or buried in the graveyard, by the rules (see
+  /// `Ruleset::removed`), such as the honor rule
+  HonorRemoval { player: Player, card: C },
+  /// A player could not flip a card, and is out of the game
+  Elimination { player: Player, turn: u64 },
+  /// A player wins the round, and claims `loot` cards for their discard
+  RoundWin {
+    player: Player,
+    loot: usize,
+    /// The number of cards owned by each player after the round
+    #[serde(serialize_with = "serialize_counts")]
+    cards: &'a [PlayerDeck<C>],
+  },
+  /// The game is over
+  GameEnd { result: GameResult, turns: u64 },
+}
+
+/// How the cards of a single battle compare under `Params::comparison`: by their keys (see
+/// `Ranked::key`), except for the special cards of the battle.
+#[derive(Clone, Copy)]
+pub enum BattleKeys {
+  /// No card is special, as under `Comparison::Rank`
+  Plain(SuitRules),
+  Special {
+    rules: SuitRules,
+    /// The key of every joker, if they are wild: that of the highest other card
+    wild: Option<u32>,
+    /// The rank that loses to every other, because a card of the lowest rank was flipped against it
+    killed: Option<u8>,
+  },
+}
+
+impl<C: Ranked> Keys<C> for BattleKeys {
+  #[inline(always)]
+  fn key(self, card: C) -> u32 {
+    let (rules, wild, killed) = match self {
+      Self::Plain(rules) => return card.key(rules),
+      Self::Special {
+        rules,
+        wild,
+        killed,
+      } => (rules, wild, killed),
+    };
+
+    if let Some(wild) = wild {
+      if card.is_joker() {
+        return wild;
+      }
+    }
+    if killed == Some(card.rank()) {
+      return 0;
+    }
+    card.key(rules)
+  }
+
+  fn plain(self) -> Option<SuitRules> {
+    match self {
+      Self::Plain(rules) => Some(rules),
+      Self::Special { .. } => None,
+    }
+  }
+}
+
+/// The highest of the cards flipped in a battle and its key, the first player who flipped it, and
+/// the number of players who flipped a card of the same key. Of tied cards, the highest card is the
+/// one of the lowest rank, which is the card that a wild joker copies, whatever the seats.
+fn highest<C: Ranked>(
+  players: &[Player],
+  cards: &[C],
+  keys: impl Keys<C>,
+) -> (Player, C, u32, usize) {
+  // Two cards is by far the most common case, so it is decided without a loop (or branches)
+  if let (&[player1, player2], &[card1, card2]) = (players, cards) {
+    let (key1, key2) = (keys.key(card1), keys.key(card2));
+    let n_high = if key1 == key2 { 2 } else { 1 };
+    let (leader, high, key) = if key1 >= key2 {
+      (player1, card1, key1)
+    } else {
+      (player2, card2, key2)
+    };
+    let high = if key1 == key2 && card2.rank() < card1.rank() {
+      card2
+    } else {
+      high
+    };
+    return (leader, high, key, n_high);
+  }
+
+  let (mut leader, mut high, mut high_key, mut n_high) =
+    (players[0], cards[0], keys.key(cards[0]), 1);
+  for (&player, &card) in players[1..].iter().zip(&cards[1..]) {
+    let card_key = keys.key(card);
+    if card_key > high_key {
+      (leader, high, high_key, n_high) = (player, card, card_key, 1);
+    } else if card_key == high_key {
+      n_high += 1;
+      if card.rank() < high.rank() {
+        high = card;
+      }
+    }
+  }
+  (leader, high, high_key, n_high)
+}
+
+/// Serializes players' decks as the number of cards each player owns.
+fn serialize_counts<S: Serializer, C>(
+  players: &&[PlayerDeck<C>],
+  serializer: S,
+) -> Result<S::Ok, S::Error> {
+  serializer.collect_seq(players.iter().map(PlayerDeck::cards))
+}
+
+/// Receives every event of a game as it is played.
+pub trait Observer<C = u8> {
+  fn observe(&mut self, event: &Event<C>);
+}
+
+/// The unit observer ignores every event, so that unobserved games are not slowed down.
+impl<C> Observer<C> for () {
+  #[inline(always)]
+  fn observe(&mut self, _: &Event<C>) {}
+}
+
+/// The keys by which the cards flipped in one battle are compared (see `Ruleset::battle`).
+pub trait Keys<C>: Copy {
+  /// The highest key wins the battle, and equal highest keys start a war.
+  fn key(self, card: C) -> u32;
+
+  /// If every card's key is its own (see `Ranked::key`), the suit rules it uses. The game then
+  /// compares the cards without calling `key`, which is much faster for bare ranks.
+  fn plain(self) -> Option<SuitRules> {
+    None
+  }
+}
+
+/// The keys of battles without any special card.
+#[derive(Clone, Copy)]
+struct PlainKeys(SuitRules);
+
+impl<C: Ranked> Keys<C> for PlainKeys {
+  #[inline(always)]
+  fn key(self, card: C) -> u32 {
+    card.key(self.0)
+  }
+}
+
+/// The cards flipped in one battle, after which the rules may remove cards (see
+/// `Ruleset::removed`).
+#[derive(Clone, Copy)]
+pub struct Battle<'a, C, K> {
+  /// The flipped cards, and the players who flipped them
+  pub cards: &'a [C],
+  pub players: &'a [Player],
+  pub keys: K,
+  /// The highest of the cards, and its key
+  pub high: C,
+  pub high_key: u32,
+  /// Whether more than one player flipped the highest key, so that the battle is a tie
+  pub tied: bool,
+}
+
+/// The cards played in a round before its current battle, and the number of wars so far.
+#[derive(Clone, Copy)]
+pub struct Round<'a, C> {
+  /// The cards, in the order they were played, and the players who played them
+  pub cards: &'a [C],
+  pub players: &'a [Player],
+  pub wars: usize,
+}
+
+/// The rules of a variant of War, as hooks called by `Game` at each step of a round. `Params`
+/// implements the built-in variants; other variants can be added as separate types, without
+/// changing the game itself.
+pub trait Ruleset<C: Ranked>: Clone {
+  type Keys: Keys<C>;
+  type Strategy: Strategy<C>;
+
+  /// The settings of the game that `Game` applies itself, whatever the variant.
+  fn settings(&self) -> Settings;
+
+  /// Whether these rules play like the standard game, apart from the turn limit, cycle detection,
+  /// and the cards they remove: with the default `Settings`, battles compared by `Ranked::key`
+  /// without suit rules, and loot kept in the order it was played. The game then plays without
+  /// calling `battle` or `order_loot`, which is much faster.
+  fn standard(&self) -> bool {
+    false
+  }
+
+  /// Called once with every card of the game, before the first round.
+  fn deal(&mut self, _cards: impl Iterator<Item = C> + Clone) {}
+
+  /// How the cards flipped in a battle compare.
+  fn battle(&self, cards: &[C]) -> Self::Keys;
+
+  /// The number of cards `player` plays face-down in the `war`-th war of a round (from 1) between
+  /// cards like `high`. Players short of cards play fewer (see `OutOfCards`).
+  fn face_down(&self, player: Player, high: C, war: usize) -> usize;
+
+  /// Whether any card may ever be removed from the game, so that `removed` is worth calling.
+  fn removes(&self) -> bool;
+
+  /// The cards removed from the game after `battle`, by their index in the win pile of the
+  /// `round`: first the cards played earlier in the round, then those flipped in the battle. The
+  /// indices must be increasing.
+  fn removed<K: Keys<C>>(
+    &self,
+    battle: Battle<C, K>,
+    round: Round<C>,
+  ) -> impl Iterator<Item = usize>;
+
+  /// Whether removed cards are buried in a graveyard shared by every player, rather than leaving
+  /// the game. The graveyard is added to the loot when the next war begins.
+  fn buries(&self) -> bool;
+
+  /// How `player` chooses which card of their hand to play, if players hold hands (see
+  /// `Settings::hand`).
+  fn strategy(&self, player: Player) -> Self::Strategy;
+
+  /// Arranges the `loot` won in a round by `winner`, played by `owners`, before it is added to
+  /// their discard. The cards are in the order they were played.
+  fn order_loot(&self, winner: Player, loot: &mut Vec<C>, owners: &[Player], rng: &mut Rng);
 }
 
 /// The cards owned by one player. Cards are drawn from the deck, until it is empty,
-/// at which point the entire discard is shuffled to become the new deck.
-#[derive(Clone)]
-pub struct PlayerDeck {
-  deck: Vec<u8>,
-  discard: Vec<u8>,
+/// at which point the entire discard becomes the new deck (see `Refill`).
+/// If players hold hands, the cards flipped in battles are played from the hand, which is refilled
+/// from the deck.
+#[derive(Clone, Hash)]
+pub struct PlayerDeck<C = u8> {
+  deck: Vec<C>,
+  discard: Vec<C>,
+  hand: Vec<C>,
 }
 
-impl PlayerDeck {
-  pub fn new(deck: Vec<u8>) -> Self {
+impl<C> PlayerDeck<C> {
+  pub fn new(deck: Vec<C>) -> Self {
     Self {
       deck: Vec::new(),
       discard: deck,
+      hand: Vec::new(),
     }
   }
 
   fn cards(&self) -> usize {
-    self.deck.len() + self.discard.len()
+    self.deck.len() + self.discard.len() + self.hand.len()
   }
+}
 
-  fn draw(&mut self, rng: &mut Rng) -> Option<u8> {
+impl<C: Copy> PlayerDeck<C> {
+  fn draw(
+    &mut self,
+    player: Player,
+    refill: Refill,
+    rng: &mut Rng,
+    observer: &mut impl Observer<C>,
+  ) -> Option<C> {
     if self.deck.is_empty() {
-      rng.shuffle(&mut self.discard);
+      match refill {
+        Refill::Shuffle => {
+          if !self.discard.is_empty() {
+            let cards = self.discard.len();
+            observer.observe(&Event::Reshuffle { player, cards });
+          }
+
+          rng.shuffle(&mut self.discard);
+        }
+
+        // The deck is drawn from the back, so the first card won must be moved there
+        Refill::Keep => self.discard.reverse(),
+      }
+
       std::mem::swap(&mut self.deck, &mut self.discard);
     }
 
     self.deck.pop()
   }
 
-  fn win_loot(&mut self, cards: &[u8]) {
+  /// Draws a card to play face-down, from the hand once the deck and discard are empty.
+  fn draw_face_down(
+    &mut self,
+    player: Player,
+    refill: Refill,
+    rng: &mut Rng,
+    observer: &mut impl Observer<C>,
+  ) -> Option<C> {
+    self
+      .draw(player, refill, rng, observer)
+      .or_else(|| self.hand.pop())
+  }
+
+  /// Draws cards into the hand until it holds `size` cards, or the player is out of cards.
+  fn fill_hand(
+    &mut self,
+    size: usize,
+    player: Player,
+    refill: Refill,
+    rng: &mut Rng,
+    observer: &mut impl Observer<C>,
+  ) {
+    while self.hand.len() < size {
+      match self.draw(player, refill, rng, observer) {
+        Some(card) => self.hand.push(card),
+        None => break,
+      }
+    }
+  }
+
+  fn win_loot(&mut self, cards: &[C]) {
     self.discard.extend_from_slice(cards);
   }
 }
 
-#[derive(Clone, Copy)]
+/// What happens to a player's discard when their deck runs out.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Refill {
+  /// The discard is shuffled to become the new deck
+  #[default]
+  Shuffle,
+  /// The discard becomes the new deck in the order the cards were won. This is the same as placing
+  /// won cards directly at the bottom of the deck, and the game never shuffles after the deal
+  Keep,
+}
+
+/// How many cards each player puts face-down in a war.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WarSize {
+  /// `k` cards
+  #[default]
+  Fixed,
+  /// As many cards as the number on the tied cards: four for a tie on fours, and ten for court cards,
+  /// aces, and jokers
+  Rank,
+  /// `k` cards in the first war of a round, one more in the second war, and so on
+  Escalating,
+}
+
+impl WarSize {
+  /// The number of cards played face-down in the `war`-th war of a round (from 1) between cards
+  /// of `rank`.
+  pub fn face_down(self, k: usize, rank: u8, war: usize) -> usize {
+    match self {
+      Self::Fixed => k,
+      Self::Rank => (rank as usize + 1).min(10),
+      Self::Escalating => k + war - 1,
+    }
+  }
+}
+
+/// Which cards are removed from the game when a card is dishonored, by losing a battle by the honor
+/// threshold or less.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HonorRule {
+  /// The losing card
+  #[default]
+  Loser,
+  /// The losing card and the winning card. A tied highest card is never removed
+  Both,
+  /// The winning card instead of the losing card. A tied highest card is never removed
+  Winner,
+  /// The losing card, and if the battle ends a war, every other card its player played in the round
+  WarCards,
+  /// The losing card is buried in a graveyard shared by every player, which is added to the loot
+  /// when the next war begins
+  Graveyard,
+}
+
+/// What a player does when they own too few cards to play a war in full: the face-down cards and
+/// one more to flip.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutOfCards {
+  /// They play all but one of their cards face-down, and flip the last one. A player with no card
+  /// left to flip is eliminated, so that the game is a draw if every contender is out of cards
+  #[default]
+  FlipLast,
+  /// They lose the game immediately, and their remaining cards go to the winner of the round
+  Lose,
+  /// As `flip-last`, except that a player with no card left to flip reuses the card they flipped
+  /// last, which stays in the battle until the war is won. Only if every contender is out of cards
+  /// are they eliminated
+  ReuseLast,
+}
+
+/// How the cards flipped in a battle are compared.
+#[derive(Clone, Copy, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Comparison {
+  /// The higher rank wins, and jokers beat every other card
+  #[default]
+  Rank,
+  /// The higher rank wins, except that the lowest rank of the deck beats the highest (not counting
+  /// jokers): a two kills an ace. A two, a king, and an ace flipped together are won by the king
+  LowBeatsHigh,
+  /// Jokers are wild: each takes the rank of the highest other card flipped, and so starts a war
+  /// against it
+  WildJokers,
+}
+
+/// The order in which the cards won in a round are added to the winner's discard.
+#[derive(Clone, Copy, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LootOrder {
+  /// The order in which the cards were played, alternating between the players
+  #[default]
+  AsPlayed,
+  /// The winner's cards, then the loser's cards, each in the order they were played
+  WinnerFirst,
+  /// The loser's cards, then the winner's cards, each in the order they were played
+  LoserFirst,
+  /// A random order
+  Random,
+}
+
+/// The settings of a game that `Game` applies itself under every `Ruleset`: when to stop a game
+/// that may not end, how discards are refilled, what a player short of cards does in a war, and
+/// hands.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Settings {
+  /// The game is stopped as a `GameResult::Timeout` after this many turns
+  pub max_turns: Option<u64>,
+  /// If the game returns to an earlier state, it is stopped as a `GameResult::Cycle`.
+  /// This requires hashing the state every turn, so is disabled by default
+  pub detect_cycles: bool,
+  pub refill: Refill,
+  pub out_of_cards: OutOfCards,
+  /// If nonzero, each player holds a hand of this many cards, and plays the card of their choice in
+  /// each battle
+  pub hand: usize,
+}
+
+#[derive(Clone)]
 pub struct Params {
-  /// k cards are flipped face-down in a war
+  /// k cards are flipped face-down in a war, or in the first war of a round (see `WarSize`)
   k: usize,
-  /// If a card loses a battle by honor_threshold or less, it is removed from the game
+  war_size: WarSize,
+  /// If a card loses a battle by honor_threshold or less, the honor rule removes it from the game
+  /// (or other cards, see `HonorRule`)
   honor_threshold: u8,
+  honor_rule: HonorRule,
+  /// Overrides of `k` for each player, in seat order like `strategies`, so that players may put
+  /// down different numbers of cards in a war. Every player uses `k` if there are none
+  player_k: Vec<usize>,
+  /// Overrides of `honor_threshold` for each player, in seat order like `player_k`. A player with
+  /// a threshold of 0 is not subject to the honor rule
+  player_honor_thresholds: Vec<u8>,
+  settings: Settings,
+  /// The strategy of each player for playing from their hand, in seat order. The last one is also
+  /// used by the remaining players, and every player plays at random if there are none
+  strategies: Vec<PlayStrategy>,
+  loot_order: LootOrder,
+  /// How each player arranges the cards they win, in seat order like `strategies`. Players without
+  /// one follow `loot_order`
+  loot_strategies: Vec<LootStrategy>,
+  /// Whether suits decide battles. Such games must be played with `Card`s, not bare ranks
+  suit_rules: SuitRules,
+  comparison: Comparison,
+  /// The lowest and highest ranks of the deck, not counting jokers, if `Comparison::LowBeatsHigh`.
+  /// Learned from the deal
+  bottom_rank: u8,
+  top_rank: u8,
 }
 
 impl Default for Params {
@@ -68,90 +554,1297 @@ impl Default for Params {
   }
 }
 
+/// The rules given on the command line or in a scenario file.
+impl From<&ParamsArgs> for Params {
+  fn from(args: &ParamsArgs) -> Self {
+    Params::new(args.k, args.honor_threshold)
+      .with_honor_rule(args.honor_rule)
+      .with_player_k(args.player_k.clone())
+      .with_player_honor_thresholds(args.player_honor_threshold.clone())
+      .with_max_turns(args.max_turns)
+      .with_cycle_detection(args.detect_cycles)
+      .with_refill(args.refill)
+      .with_out_of_cards(args.out_of_cards)
+      .with_hand(args.hand, args.strategy.clone())
+      .with_loot_order(args.loot_order)
+      .with_loot_strategies(args.loot_strategy.clone())
+      .with_war_size(args.war_size)
+      .with_comparison(args.comparison)
+      .with_suit_rules(SuitRules {
+        trump: args.trump,
+        tie_break: args.suit_tie_break,
+      })
+  }
+}
+
 impl Params {
   pub fn new(k: usize, honor_threshold: u8) -> Self {
-    Self { k, honor_threshold }
+    Self {
+      k,
+      war_size: WarSize::default(),
+      honor_threshold,
+      honor_rule: HonorRule::default(),
+      player_k: Vec::new(),
+      player_honor_thresholds: Vec::new(),
+      settings: Settings::default(),
+      strategies: Vec::new(),
+      loot_order: LootOrder::default(),
+      loot_strategies: Vec::new(),
+      suit_rules: SuitRules::default(),
+      comparison: Comparison::default(),
+      bottom_rank: 0,
+      top_rank: 0,
+    }
+  }
+
+  pub fn with_war_size(self, war_size: WarSize) -> Self {
+    Self { war_size, ..self }
+  }
+
+  pub fn with_honor_rule(self, honor_rule: HonorRule) -> Self {
+    Self { honor_rule, ..self }
+  }
+
+  pub fn with_player_k(self, player_k: Vec<usize>) -> Self {
+    Self { player_k, ..self }
+  }
+
+  pub fn with_player_honor_thresholds(self, player_honor_thresholds: Vec<u8>) -> Self {
+    Self {
+      player_honor_thresholds,
+      ..self
+    }
+  }
+
+  /// These rules, with the `k` of `player` of `players` set to `k`, and every other player's
+  /// unchanged.
+  pub fn with_k_for(self, player: Player, players: usize, k: usize) -> Self {
+    let player_k = override_for(&self.player_k, self.k, player, players, k);
+    Self { player_k, ..self }
+  }
+
+  /// These rules, with the honor threshold of `player` of `players` set to `honor_threshold`, and
+  /// every other player's unchanged.
+  pub fn with_honor_threshold_for(
+    self,
+    player: Player,
+    players: usize,
+    honor_threshold: u8,
+  ) -> Self {
+    let player_honor_thresholds = override_for(
+      &self.player_honor_thresholds,
+      self.honor_threshold,
+      player,
+      players,
+      honor_threshold,
+    );
+    Self {
+      player_honor_thresholds,
+      ..self
+    }
+  }
+
+  pub fn with_max_turns(self, max_turns: Option<u64>) -> Self {
+    let settings = Settings {
+      max_turns,
+      ..self.settings
+    };
+    Self { settings, ..self }
+  }
+
+  pub fn with_cycle_detection(self, detect_cycles: bool) -> Self {
+    let settings = Settings {
+      detect_cycles,
+      ..self.settings
+    };
+    Self { settings, ..self }
+  }
+
+  pub fn with_refill(self, refill: Refill) -> Self {
+    let settings = Settings {
+      refill,
+      ..self.settings
+    };
+    Self { settings, ..self }
+  }
+
+  pub fn with_out_of_cards(self, out_of_cards: OutOfCards) -> Self {
+    let settings = Settings {
+      out_of_cards,
+      ..self.settings
+    };
+    Self { settings, ..self }
+  }
+
+  pub fn with_hand(self, hand: usize, strategies: Vec<PlayStrategy>) -> Self {
+    let settings = Settings {
+      hand,
+      ..self.settings
+    };
+    Self {
+      settings,
+      strategies,
+      ..self
+    }
+  }
+
+  pub fn with_loot_order(self, loot_order: LootOrder) -> Self {
+    Self { loot_order, ..self }
+  }
+
+  pub fn with_loot_strategies(self, loot_strategies: Vec<LootStrategy>) -> Self {
+    Self {
+      loot_strategies,
+      ..self
+    }
+  }
+
+  pub fn with_suit_rules(self, suit_rules: SuitRules) -> Self {
+    Self { suit_rules, ..self }
+  }
+
+  pub fn with_comparison(self, comparison: Comparison) -> Self {
+    Self { comparison, ..self }
+  }
+
+  /// Whether games under these rules must be played with `Card`s, because suits decide battles.
+  pub fn uses_suits(&self) -> bool {
+    self.suit_rules.any()
+  }
+
+  /// Whether a card played by `player` that lost a battle to `winner` is dishonored, by losing by
+  /// the player's honor threshold or less, unless it is 0. A trump or a two may beat a card of
+  /// higher rank, which is no shame.
+  fn dishonored<C: Ranked>(&self, player: Player, loser: C, winner: C) -> bool {
+    let threshold = for_player_or(&self.player_honor_thresholds, player, self.honor_threshold);
+    threshold > 0
+      && (winner.rank())
+        .checked_sub(loser.rank())
+        .is_some_and(|margin| margin <= threshold)
+  }
+
+  /// Whether the card flipped at index `i` of a battle is dishonored.
+  fn dishonored_in<C: Ranked, K: Keys<C>>(&self, i: usize, battle: Battle<C, K>) -> bool {
+    let (player, card) = (battle.players[i], battle.cards[i]);
+    battle.keys.key(card) != battle.high_key && self.dishonored(player, card, battle.high)
+  }
+
+  /// The indices of the win pile of a round that the honor rule may remove after a battle: those of
+  /// the battle, and also those of the rest of the round if `HonorRule::WarCards` ends a war.
+  #[cold]
+  fn honor_range<C: Ranked, K: Keys<C>>(
+    &self,
+    battle: Battle<C, K>,
+    round: Round<C>,
+  ) -> Range<usize> {
+    let start = round.cards.len();
+    let war_ended = round.wars > 0 && !battle.tied;
+    let first = match self.honor_rule {
+      HonorRule::WarCards if war_ended => 0,
+      _ => start,
+    };
+    first..start + battle.cards.len()
+  }
+
+  /// Whether the honor rule removes the card at index `i` of the win pile of a round after a
+  /// battle.
+  #[cold]
+  fn removed_by_honor<C: Ranked, K: Keys<C>>(
+    &self,
+    i: usize,
+    battle: Battle<C, K>,
+    round: Round<C>,
+  ) -> bool {
+    let flipped = 0..battle.cards.len();
+    let dishonored = |j| self.dishonored_in(j, battle);
+
+    // The earlier cards of the round are only removed from players whose card was dishonored
+    let Some(i) = i.checked_sub(round.cards.len()) else {
+      let player = round.players[i];
+      return flipped
+        .clone()
+        .any(|j| battle.players[j] == player && dishonored(j));
+    };
+
+    // A tied highest card is never removed
+    let won = !battle.tied && battle.keys.key(battle.cards[i]) == battle.high_key;
+    match self.honor_rule {
+      HonorRule::Loser | HonorRule::Graveyard | HonorRule::WarCards => dishonored(i),
+      HonorRule::Both => dishonored(i) || (won && flipped.clone().any(dishonored)),
+      HonorRule::Winner => won && flipped.clone().any(dishonored),
+    }
+  }
+}
+
+/// The setting of `player` in a list of per-player settings in seat order, where the last one is
+/// also used by the remaining players.
+fn for_player<T: Copy + Default>(settings: &[T], player: Player) -> T {
+  let setting = settings.get(player.0).or(settings.last());
+  setting.copied().unwrap_or_default()
+}
+
+/// The setting of `player` as in `for_player`, or `default` if there are none.
+fn for_player_or<T: Copy>(settings: &[T], player: Player, default: T) -> T {
+  let setting = settings.get(player.0).or(settings.last());
+  setting.copied().unwrap_or(default)
+}
+
+/// The per-player settings of `players` players, where `player`'s is `value` and every other
+/// player's is as in `settings`, or `default` if there are none.
+fn override_for<T: Copy>(
+  settings: &[T],
+  default: T,
+  player: Player,
+  players: usize,
+  value: T,
+) -> Vec<T> {
+  let setting = |i| for_player_or(settings, Player(i), default);
+  let mut overridden: Vec<_> = (0..players).map(setting).collect();
+  overridden[player.0] = value;
+  overridden
+}
+
+/// The built-in rules: the war sizes of `WarSize`, the honor rule, the comparisons of
+/// `Comparison` and `SuitRules`, the play strategies of `PlayStrategy`, and the loot orders of
+/// `LootOrder` and `LootStrategy`.
+impl<C: Ranked> Ruleset<C> for Params {
+  type Keys = BattleKeys;
+  type Strategy = BuiltinStrategy;
+
+  fn settings(&self) -> Settings {
+    self.settings
+  }
+
+  fn standard(&self) -> bool {
+    let settings = Settings {
+      max_turns: None,
+      detect_cycles: false,
+      ..self.settings
+    };
+    let plain = |strategy: &LootStrategy| *strategy == LootStrategy::Rules;
+    settings == Settings::default()
+      && matches!(self.comparison, Comparison::Rank)
+      && !self.suit_rules.any()
+      && matches!(self.loot_order, LootOrder::AsPlayed)
+      && self.loot_strategies.iter().all(plain)
+  }
+
+  fn deal(&mut self, cards: impl Iterator<Item = C> + Clone) {
+    // Only the lowest-beats-highest rule needs the range of ranks
+    if let Comparison::LowBeatsHigh = self.comparison {
+      let ranks = cards.map(|card| card.rank()).filter(|&rank| rank != JOKER);
+      self.bottom_rank = ranks.clone().min().unwrap_or(0);
+      self.top_rank = ranks.max().unwrap_or(0);
+    }
+  }
+
+  #[inline(always)]
+  fn battle(&self, cards: &[C]) -> BattleKeys {
+    let rules = self.suit_rules;
+    let (wild, killed) = match self.comparison {
+      Comparison::Rank => return BattleKeys::Plain(rules),
+      Comparison::LowBeatsHigh => {
+        let killer = cards.iter().any(|card| card.rank() == self.bottom_rank);
+        (None, killer.then_some(self.top_rank))
+      }
+      Comparison::WildJokers => {
+        let others = cards.iter().filter(|card| !card.is_joker());
+        (others.map(|card| card.key(rules)).max(), None)
+      }
+    };
+
+    BattleKeys::Special {
+      rules,
+      wild,
+      killed,
+    }
+  }
+
+  fn face_down(&self, player: Player, high: C, war: usize) -> usize {
+    let k = for_player_or(&self.player_k, player, self.k);
+    self.war_size.face_down(k, high.rank(), war)
+  }
+
+  fn removes(&self) -> bool {
+    self.honor_threshold > 0 || !self.player_honor_thresholds.is_empty()
+  }
+
+  /// The cards removed by the honor rule, if a card of the battle was dishonored (see `HonorRule`).
+  #[inline(always)]
+  fn removed<K: Keys<C>>(
+    &self,
+    battle: Battle<C, K>,
+    round: Round<C>,
+  ) -> impl Iterator<Item = usize> {
+    let rule = self.honor_rule;
+    let start = round.cards.len();
+
+    // Two cards under the default rule is by far the most common case, so only the losing card is
+    // checked, without a loop
+    let pair = match (rule, battle.cards) {
+      (HonorRule::Loser, &[card1, card2]) => {
+        let loser = usize::from(battle.keys.key(card1) >= battle.keys.key(card2));
+        Some(if self.dishonored_in(loser, battle) {
+          start + loser..start + loser + 1
+        } else {
+          0..0
+        })
+      }
+      _ => None,
+    };
+    let decided = pair.is_some();
+    let indices = pair.unwrap_or_else(|| self.honor_range(battle, round));
+
+    indices.filter(move |&i| decided || self.removed_by_honor(i, battle, round))
+  }
+
+  fn buries(&self) -> bool {
+    self.honor_rule == HonorRule::Graveyard
+  }
+
+  fn strategy(&self, player: Player) -> BuiltinStrategy {
+    BuiltinStrategy {
+      strategy: for_player(&self.strategies, player),
+      rules: self.suit_rules,
+    }
   }
+
+  fn order_loot(&self, winner: Player, loot: &mut Vec<C>, owners: &[Player], rng: &mut Rng) {
+    // The winner may arrange their loot themselves
+    let strategy = for_player(&self.loot_strategies, winner);
+    if strategy != LootStrategy::Rules {
+      return strategy.arrange(loot, self.suit_rules, rng);
+    }
+
+    let winner_first = match self.loot_order {
+      LootOrder::AsPlayed => return,
+      LootOrder::Random => return rng.shuffle(loot),
+      LootOrder::WinnerFirst => true,
+      LootOrder::LoserFirst => false,
+    };
+
+    let (firsts, seconds): (Vec<_>, Vec<_>) = owners
+      .iter()
+      .zip(loot.iter())
+      .partition(|&(&owner, _)| (owner == winner) == winner_first);
+
+    *loot = firsts
+      .into_iter()
+      .chain(seconds)
+      .map(|(_, &card)| card)
+      .collect();
+  }
+}
+
+/// A player with a hand refills it, then plays the card of their choice, knowing the cards each
+/// player is `known` to hold. Returns `None` if they are out of cards. Kept out of line, since only
+/// games with hands play it.
+#[inline(never)]
+fn play_hand<C: Ranked, R: Ruleset<C>>(
+  rules: &R,
+  deck: &mut PlayerDeck<C>,
+  player: Player,
+  known: &[Vec<C>],
+  rng: &mut Rng,
+  observer: &mut impl Observer<C>,
+) -> Option<C> {
+  let Settings { hand, refill, .. } = rules.settings();
+  deck.fill_hand(hand, player, refill, rng, observer);
+  let strategy = rules.strategy(player);
+  (!deck.hand.is_empty()).then(|| {
+    let i = strategy.choose(player, &deck.hand, known, rng);
+    deck.hand.swap_remove(i)
+  })
+}
+
+/// Forgets one copy of `card` from the cards a player is known to hold, once they have played it.
+fn forget<C: PartialEq>(known: &mut Vec<C>, card: C) {
+  if let Some(i) = known.iter().position(|known| *known == card) {
+    known.swap_remove(i);
+  }
+}
+
+/// Moves the card `player` flipped last to the end of the win pile `work`, played by `owners`, to be
+/// flipped again in the next battle of a war. The cards played since then move down by one.
+///
+/// The last battle ended the win pile at `flips_end`, before the graveyard and the face-down cards
+/// were added, so only the cards before it are searched: a buried card keeps its original owner.
+#[cold]
+fn reuse_last<C>(
+  work: &mut Vec<C>,
+  owners: &mut Vec<Player>,
+  flips_end: &mut usize,
+  player: Player,
+) {
+  let last = owners[..*flips_end]
+    .iter()
+    .rposition(|&owner| owner == player)
+    .unwrap();
+  let card = work.remove(last);
+  owners.remove(last);
+  work.push(card);
+  owners.push(player);
+  *flips_end -= 1;
 }
 
-pub struct Game {
-  params: Params,
+pub struct Game<C = u8, R = Params> {
+  rules: R,
   rng: Rng,
-  player1: PlayerDeck,
-  player2: PlayerDeck,
+  players: Vec<PlayerDeck<C>>,
+  /// The turn on which each player was eliminated, if they have been
+  eliminated: Vec<Option<u64>>,
 
   /// A workspace vector, storing all the cards won in a single round
-  work: Vec<u8>,
+  work: Vec<C>,
+  /// The player who played each card of `work`
+  work_owners: Vec<Player>,
+  /// The players still in the game
+  active: Vec<Player>,
+  /// A workspace vector, storing the players still contending the current round
+  contenders: Vec<Player>,
+  /// The cards buried by the rules (see `Ruleset::buries`), and the players who played them
+  graveyard: Vec<C>,
+  graveyard_owners: Vec<Player>,
+  /// A workspace vector, storing the indices in `work` of the cards removed after a battle
+  removed: Vec<usize>,
+  /// The cards each player is known to hold, if players hold hands: those they won in a round, and
+  /// have not played since
+  known: Vec<Vec<C>>,
+  /// Hashes of the states at the start of every turn so far, if detecting cycles
+  seen: HashSet<u64>,
 }
 
-impl Game {
-  /// Create (but do not simulate) a new game with the given player decks.
-  pub fn new(params: Params, rng: Rng, player1: PlayerDeck, player2: PlayerDeck) -> Self {
+impl<C: Ranked, R: Ruleset<C>> Game<C, R> {
+  /// Create (but do not simulate) a new game with the given player decks, in seat order.
+  /// If discards are not shuffled, the initial decks are shuffled once here instead, as they would
+  /// be by the deal.
+  pub fn new(mut rules: R, mut rng: Rng, mut players: Vec<PlayerDeck<C>>) -> Self {
+    if let Refill::Keep = rules.settings().refill {
+      for player in &mut players {
+        rng.shuffle(&mut player.discard);
+      }
+    }
+
+    rules.deal(
+      players
+        .iter()
+        .flat_map(|player| player.discard.iter().copied()),
+    );
+
     Self {
-      params,
+      rules,
       rng,
-      player1,
-      player2,
+      eliminated: vec![None; players.len()],
+      active: (0..players.len()).map(Player).collect(),
+      known: vec![Vec::new(); players.len()],
+      players,
       work: Vec::new(),
+      work_owners: Vec::new(),
+      contenders: Vec::new(),
+      graveyard: Vec::new(),
+      graveyard_owners: Vec::new(),
+      removed: Vec::new(),
+      seen: HashSet::new(),
+    }
+  }
+
+  /// The turn on which each player ran out of cards and left the game, or `None` for the players
+  /// left at the end of the game.
+  pub fn eliminations(&self) -> &[Option<u64>] {
+    &self.eliminated
+  }
+
+  /// A hash of the state of the game at the start of a turn: every player's deck, discard, and hand
+  /// in order, the cards of the graveyard (but not who played them), the cards each player is known
+  /// to hold, and the current state of the random number generator (`Rng::get_seed`), which
+  /// advances on every random draw, such as shuffling a discard or a random strategy's choice.
+  fn state_hash(&self) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let state = (&self.players, &self.graveyard, &self.known);
+    (state, self.rng.get_seed()).hash(&mut hasher);
+    hasher.finish()
+  }
+
+  /// The settings of the rules. If `STANDARD`, only the turn limit and cycle detection may differ
+  /// from the defaults, so that the others are constants.
+  fn settings<const STANDARD: bool>(&self) -> Settings {
+    let settings = self.rules.settings();
+    if !STANDARD {
+      return settings;
+    }
+
+    Settings {
+      max_turns: settings.max_turns,
+      detect_cycles: settings.detect_cycles,
+      ..Settings::default()
+    }
+  }
+
+  /// The result of the game if at most one player remains.
+  fn game_over(&self) -> Option<GameResult> {
+    match self.active[..] {
+      [] => Some(GameResult::Draw),
+      [player] => Some(GameResult::Win(player)),
+      _ => None,
     }
   }
 
-  fn play_round(&mut self) -> RoundResult {
-    let Params { k, honor_threshold } = self.params;
+  fn play_round<const STANDARD: bool, const REMOVES: bool>(
+    &mut self,
+    turn: u64,
+    observer: &mut impl Observer<C>,
+  ) -> RoundResult {
+    let Settings {
+      refill,
+      out_of_cards,
+      hand,
+      ..
+    } = self.settings::<STANDARD>();
     self.work.clear();
+    self.work_owners.clear();
+
+    // Every player still in the game contends the first battle of the round, and only the tied
+    // players contend each war
+    let mut wars = 0;
+    let mut flips_end = 0;
 
     loop {
-      // Each player plays a card, if possible. If they are out of cards, they have lost
-      let (card1, card2) = match (
-        self.player1.draw(&mut self.rng),
-        self.player2.draw(&mut self.rng),
-      ) {
-        (None, None) => return RoundResult::GameResult(GameResult::Draw),
-        (None, Some(_)) => return RoundResult::GameResult(GameResult::Player2),
-        (Some(_), None) => return RoundResult::GameResult(GameResult::Player1),
-        (Some(card1), Some(card2)) => (card1, card2),
+      // Each contender plays a card onto the win pile, if possible. If they are out of cards, they
+      // are eliminated, or reuse the card they flipped last
+      let contenders = if wars > 0 {
+        &self.contenders
+      } else {
+        &self.active
       };
+      let reuse = out_of_cards == OutOfCards::ReuseLast
+        && wars > 0
+        && contenders
+          .iter()
+          .any(|player| self.players[player.0].cards() > 0);
+
+      let mut start = self.work.len();
+      for &player in contenders {
+        let deck = &mut self.players[player.0];
+        let drawn = if hand == 0 {
+          deck.draw(player, refill, &mut self.rng, observer)
+        } else {
+          let known = &self.known;
+          play_hand(&self.rules, deck, player, known, &mut self.rng, observer)
+        };
+
+        match drawn {
+          Some(card) => {
+            self.work.push(card);
+            self.work_owners.push(player);
+          }
+          None if reuse => {
+            reuse_last(
+              &mut self.work,
+              &mut self.work_owners,
+              &mut flips_end,
+              player,
+            );
+            start -= 1;
+          }
+          None => {
+            self.eliminated[player.0] = Some(turn);
+            observer.observe(&Event::Elimination { player, turn });
+          }
+        }
+      }
+
+      // Every player flips at the same time, so the cards are only known to have been played once
+      // they all have. A player who reuses their last card holds no known cards
+      if hand > 0 {
+        for (&player, &card) in self.work_owners[start..].iter().zip(&self.work[start..]) {
+          forget(&mut self.known[player.0], card);
+        }
+      }
+
+      let flipped = self.work.len() - start;
+      if flipped < contenders.len() {
+        let eliminated = &self.eliminated;
+        self.active.retain(|player| eliminated[player.0].is_none());
+        if let Some(result) = self.game_over() {
+          return RoundResult::GameResult(result);
+        }
+      }
+
+      if flipped == 0 {
+        return RoundResult::NoWinner;
+      }
+
+      observer.observe(&Event::Flip {
+        players: &self.work_owners[start..],
+        cards: &self.work[start..],
+      });
 
-      // Honorable war: if the losing card lost by a small enough margin, remove it from the game.
-      // Otherwise, append both cards to the win pile.
-      if card1 != card2 && card1.abs_diff(card2) <= honor_threshold {
-        self.work.extend([card1.max(card2)]);
+      // Plain comparisons are by far the most common, so they get their own copy of `battle`. The
+      // standard rules always compare plainly, without asking the rules
+      let battle = if STANDARD {
+        self.battle::<REMOVES>(start, PlainKeys(SuitRules::default()), wars, observer)
       } else {
-        self.work.extend([card1, card2]);
+        let keys = self.rules.battle(&self.work[start..]);
+        match keys.plain() {
+          Some(rules) => self.battle::<REMOVES>(start, PlainKeys(rules), wars, observer),
+          None => self.battle::<REMOVES>(start, keys, wars, observer),
+        }
+      };
+
+      // If one player flipped the highest card, they win the round
+      // If several did, each of them plays face-down cards and they repeat
+      let high = match battle {
+        Ok(leader) => return RoundResult::RoundWin(leader),
+        Err(high) => high,
+      };
+      flips_end = self.work.len();
+
+      wars += 1;
+      observer.observe(&Event::War {
+        rank: high.rank(),
+        players: &self.contenders,
+      });
+
+      // The graveyard is added to the loot of every war
+      if !self.graveyard.is_empty() {
+        self.unearth_graveyard();
+      }
+
+      let mut lost = false;
+      for &player in &self.contenders {
+        let face_down = self.rules.face_down(player, high, wars);
+        let deck = &mut self.players[player.0];
+        let start = self.work.len();
+
+        // A player short of cards plays what they can, keeping one to flip, or loses
+        let cards = deck.cards();
+        let n = if cards > face_down {
+          face_down
+        } else if out_of_cards == OutOfCards::Lose {
+          self.eliminated[player.0] = Some(turn);
+          observer.observe(&Event::Elimination { player, turn });
+          lost = true;
+          cards
+        } else {
+          cards.saturating_sub(1)
+        };
+
+        for _ in 0..n {
+          let card = deck
+            .draw_face_down(player, refill, &mut self.rng, observer)
+            .unwrap();
+          if hand > 0 {
+            forget(&mut self.known[player.0], card);
+          }
+          self.work.push(card);
+          self.work_owners.push(player);
+        }
+
+        if n > 0 {
+          let cards = &self.work[start..];
+          observer.observe(&Event::FaceDown { player, cards });
+        }
+      }
+
+      if lost {
+        if let Some(result) = self.leave_war() {
+          return result;
+        }
+      }
+    }
+  }
+
+  /// Adds the graveyard to the win pile.
+  #[cold]
+  fn unearth_graveyard(&mut self) {
+    self.work.append(&mut self.graveyard);
+    self.work_owners.append(&mut self.graveyard_owners);
+  }
+
+  /// Removes the players who lost the game for being unable to play a war, returning the result of
+  /// the round if it is over.
+  #[cold]
+  fn leave_war(&mut self) -> Option<RoundResult> {
+    let eliminated = &self.eliminated;
+    self.active.retain(|player| eliminated[player.0].is_none());
+    self
+      .contenders
+      .retain(|player| eliminated[player.0].is_none());
+    if let Some(result) = self.game_over() {
+      return Some(RoundResult::GameResult(result));
+    }
+
+    match self.contenders[..] {
+      [] => Some(RoundResult::NoWinner),
+      [player] => Some(RoundResult::RoundWin(player)),
+      _ => None,
+    }
+  }
+
+  /// Decides the battle of the cards flipped from `start` in the win pile, comparing them by `key`,
+  /// after `wars` wars of the round, and removes cards if `REMOVES` and the rules do. Returns the
+  /// winner, or the highest card if it was tied, in which case the tied players become the
+  /// contenders of a war.
+  fn battle<const REMOVES: bool>(
+    &mut self,
+    start: usize,
+    keys: impl Keys<C>,
+    wars: usize,
+    observer: &mut impl Observer<C>,
+  ) -> Result<Player, C> {
+    let (leader, high, high_key, n_high) =
+      highest(&self.work_owners[start..], &self.work[start..], keys);
+
+    // The rules may remove cards after the battle, such as the honor rule, under which a card that
+    // lost to the highest card by a small enough margin leaves the game
+    if REMOVES && self.rules.removes() {
+      let battle = Battle {
+        cards: &self.work[start..],
+        players: &self.work_owners[start..],
+        keys,
+        high,
+        high_key,
+        tied: n_high > 1,
+      };
+      let round = Round {
+        cards: &self.work[..start],
+        players: &self.work_owners[..start],
+        wars,
+      };
+
+      // Most battles remove no card, and most others one, so the workspace is only filled if they
+      // remove more
+      let mut removed = self.rules.removed(battle, round);
+      if let Some(first) = removed.next() {
+        if let Some(second) = removed.next() {
+          self.removed.clear();
+          self.removed.extend([first, second]);
+          self.removed.extend(removed);
+          self.remove(observer);
+        } else {
+          drop(removed);
+          self.remove_one(first, observer);
+        }
       }
+    }
+
+    if n_high == 1 {
+      return Ok(leader);
+    }
+
+    self.contenders.clear();
+    self.contenders.extend(
+      self.work_owners[start..]
+        .iter()
+        .zip(&self.work[start..])
+        .filter(|&(_, &card)| keys.key(card) == high_key)
+        .map(|(&player, _)| player),
+    );
+    Err(high)
+  }
 
-      // If the cards are different, one player wins the round
-      // If the cards are equal, each player plays up to `k` face-down cards (leaving at least one card in their deck) and we repeat
-      match card1.cmp(&card2) {
-        Ordering::Greater => return RoundResult::RoundWin(Player::Player1),
-        Ordering::Less => return RoundResult::RoundWin(Player::Player2),
+  /// Removes the card at index `i` of the win pile from the game, or buries it in the graveyard if
+  /// the rules do.
+  fn remove_one(&mut self, i: usize, observer: &mut impl Observer<C>) {
+    let (player, card) = (self.work_owners[i], self.work[i]);
+    for j in i + 1..self.work.len() {
+      self.work[j - 1] = self.work[j];
+      self.work_owners[j - 1] = self.work_owners[j];
+    }
+    self.work.pop();
+    self.work_owners.pop();
 
-        Ordering::Equal => {
-          let n = self.player1.cards().saturating_sub(1).min(k);
-          self
-            .work
-            .extend((0..n).map(|_| self.player1.draw(&mut self.rng).unwrap()));
+    observer.observe(&Event::HonorRemoval { player, card });
+    if self.rules.buries() {
+      self.graveyard.push(card);
+      self.graveyard_owners.push(player);
+    }
+  }
 
-          let n = self.player2.cards().saturating_sub(1).min(k);
-          self
-            .work
-            .extend((0..n).map(|_| self.player2.draw(&mut self.rng).unwrap()));
+  /// Removes the cards of the win pile at the indices in `removed` from the game, or buries them in
+  /// the graveyard if the rules do.
+  fn remove(&mut self, observer: &mut impl Observer<C>) {
+    let bury = self.rules.buries();
+    let mut removed = self.removed.iter().peekable();
+    let first = self.removed[0];
+    let mut kept = first;
+    for i in first..self.work.len() {
+      let (player, card) = (self.work_owners[i], self.work[i]);
+      if removed.next_if_eq(&&i).is_some() {
+        observer.observe(&Event::HonorRemoval { player, card });
+        if bury {
+          self.graveyard.push(card);
+          self.graveyard_owners.push(player);
         }
+      } else {
+        self.work[kept] = card;
+        self.work_owners[kept] = player;
+        kept += 1;
       }
     }
+    self.work.truncate(kept);
+    self.work_owners.truncate(kept);
   }
 
   /// Plays this game to completion, returning the winner and the number of turns taken.
   pub fn play(&mut self) -> (GameResult, u64) {
+    self.play_observed(&mut ())
+  }
+
+  /// Plays this game to completion like `play`, reporting every event to the observer.
+  pub fn play_observed(&mut self, observer: &mut impl Observer<C>) -> (GameResult, u64) {
+    // Most games are played under the standard rules, so they get their own copies of the game,
+    // with and without the removal of cards
+    match (self.rules.standard(), self.rules.removes()) {
+      (true, false) => self.play_rounds::<true, false>(observer),
+      (true, true) => self.play_rounds::<true, true>(observer),
+      (false, _) => self.play_rounds::<false, true>(observer),
+    }
+  }
+
+  /// Plays this game to completion like `play_observed`. If `STANDARD`, the rules are known to be
+  /// standard (see `Ruleset::standard`), so that their hooks are replaced by constants, and if not
+  /// `REMOVES`, they are known never to remove a card.
+  fn play_rounds<const STANDARD: bool, const REMOVES: bool>(
+    &mut self,
+    observer: &mut impl Observer<C>,
+  ) -> (GameResult, u64) {
+    let settings = self.settings::<STANDARD>();
     let mut turn = 0;
     loop {
       turn += 1;
 
-      match self.play_round() {
-        RoundResult::RoundWin(Player::Player1) => self.player1.win_loot(&self.work),
-        RoundResult::RoundWin(Player::Player2) => self.player2.win_loot(&self.work),
-        RoundResult::GameResult(result) => return (result, turn),
+      // Stop games that run too long, or that have returned to an earlier state and so would never end
+      let unfinished = if settings.max_turns.is_some_and(|max_turns| turn > max_turns) {
+        Some(GameResult::Timeout)
+      } else if settings.detect_cycles && !self.seen.insert(self.state_hash()) {
+        Some(GameResult::Cycle)
+      } else {
+        None
+      };
+
+      if let Some(result) = unfinished {
+        let turns = turn - 1;
+        observer.observe(&Event::GameEnd { result, turns });
+        return (result, turns);
+      }
+
+      observer.observe(&Event::Round { turn });
+
+      let player = match self.play_round::<STANDARD, REMOVES>(turn, observer) {
+        RoundResult::RoundWin(player) => player,
+        RoundResult::NoWinner => continue,
+        RoundResult::GameResult(result) => {
+          observer.observe(&Event::GameEnd {
+            result,
+            turns: turn,
+          });
+          return (result, turn);
+        }
+      };
+
+      if !STANDARD {
+        self
+          .rules
+          .order_loot(player, &mut self.work, &self.work_owners, &mut self.rng);
+      }
+      self.players[player.0].win_loot(&self.work);
+      if settings.hand > 0 {
+        self.known[player.0].extend_from_slice(&self.work);
+      }
+
+      observer.observe(&Event::RoundWin {
+        player,
+        loot: self.work.len(),
+        cards: &self.players,
+      });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::card::{self, Suit};
+  use crate::record::Recorder;
+
+  const PLAYERS: [Player; 4] = [Player(0), Player(1), Player(0), Player(1)];
+
+  /// The indices removed under `rule` with an honor threshold of 1 after a battle of `cards`, flipped
+  /// by players 1, 2, and so on, after the `earlier` cards of the round, played by players 1 and 2 in
+  /// turn.
+  fn removed(rule: HonorRule, earlier: &[u8], cards: &[u8]) -> Vec<usize> {
+    let params = Params::new(3, 1).with_honor_rule(rule);
+    let keys = PlainKeys(SuitRules::default());
+    let high = *cards.iter().max().unwrap();
+    let battle = Battle {
+      cards,
+      players: &[Player(0), Player(1), Player(2)][..cards.len()],
+      keys,
+      high,
+      high_key: keys.key(high),
+      tied: cards.iter().filter(|&&card| card == high).count() > 1,
+    };
+    let round = Round {
+      cards: earlier,
+      players: &PLAYERS[..earlier.len()],
+      wars: usize::from(!earlier.is_empty()),
+    };
+    params.removed(battle, round).collect()
+  }
+
+  #[test]
+  fn honor_rules_remove_the_dishonored_battle() {
+    assert_eq!(removed(HonorRule::Loser, &[], &[3, 4]), [0]);
+    assert_eq!(removed(HonorRule::Loser, &[], &[4, 3]), [1]);
+    assert_eq!(removed(HonorRule::Graveyard, &[], &[3, 4]), [0]);
+    assert_eq!(removed(HonorRule::Both, &[], &[3, 4]), [0, 1]);
+    assert_eq!(removed(HonorRule::Winner, &[], &[3, 4]), [1]);
+    assert_eq!(removed(HonorRule::WarCards, &[], &[3, 4]), [0]);
+
+    // A card that loses by more than the threshold is not dishonored
+    for rule in HonorRule::value_variants() {
+      assert!(removed(*rule, &[], &[2, 4]).is_empty());
+    }
+  }
+
+  #[test]
+  fn honor_rules_never_remove_a_tied_card() {
+    assert_eq!(removed(HonorRule::Both, &[], &[4, 4, 3]), [2]);
+    assert!(removed(HonorRule::Winner, &[], &[4, 4, 3]).is_empty());
+  }
+
+  #[test]
+  fn war_cards_removes_the_loser_cards_of_a_won_war() {
+    // Player 1 played the first and third cards of the round
+    let earlier = [7, 7, 1, 2];
+    assert_eq!(removed(HonorRule::WarCards, &earlier, &[3, 4]), [0, 2, 4]);
+    assert_eq!(removed(HonorRule::Loser, &earlier, &[3, 4]), [4]);
+  }
+
+  #[test]
+  fn reuse_last_skips_the_graveyard() {
+    // Players 1 and 2 tied with 5s, then the graveyard, holding a card player 1 lost earlier, and
+    // the face-down card of player 2 were added
+    let mut work = vec![1, 5, 5, 2, 3];
+    let mut owners = vec![Player(0), Player(0), Player(1), Player(0), Player(1)];
+    let mut flips_end = 3;
+    reuse_last(&mut work, &mut owners, &mut flips_end, Player(0));
+    assert_eq!(work, [1, 5, 2, 3, 5]);
+    assert!(owners[4] == Player(0));
+    assert_eq!(flips_end, 2);
+  }
+
+  #[test]
+  fn tied_players_out_of_cards_are_eliminated_from_the_war() {
+    // Players 1 and 2 tie on 3s, and only player 1 has a card left to flip in the war, so player 2
+    // is eliminated and player 1 wins the round. Player 3 is eliminated on the next turn
+    let players = [vec![3, 3], vec![3], vec![1]].map(PlayerDeck::new);
+    let mut game = Game::new(Params::new(0, 0), Rng::with_seed(0), players.to_vec());
+    assert!(game.play() == (GameResult::Win(Player(0)), 2));
+    assert_eq!(game.eliminations(), [None, Some(1), Some(2)]);
+  }
+
+  #[test]
+  fn players_tied_out_of_cards_leave_the_round_to_the_others() {
+    // Players 1 and 2 tie on 3s and neither can flip in the war, so both are eliminated, and player
+    // 3 wins although the round had no winner
+    let players = [vec![3], vec![3], vec![1, 1]].map(PlayerDeck::new);
+    let mut game = Game::new(Params::new(0, 0), Rng::with_seed(0), players.to_vec());
+    assert!(game.play() == (GameResult::Win(Player(2)), 1));
+    assert_eq!(game.eliminations(), [Some(1), Some(1), None]);
+  }
+
+  #[test]
+  fn kept_discards_are_drawn_in_the_order_won() {
+    let mut deck = PlayerDeck::new(Vec::new());
+    deck.win_loot(&[1, 2]);
+    deck.win_loot(&[3]);
+    let mut rng = Rng::with_seed(0);
+    let mut draw = || deck.draw(Player(0), Refill::Keep, &mut rng, &mut ());
+    assert_eq!(
+      [draw(), draw(), draw(), draw()],
+      [Some(1), Some(2), Some(3), None]
+    );
+  }
+
+  #[test]
+  fn loot_orders_put_the_winner_or_the_loser_first() {
+    // Player 2 wins cards played by players 1 and 2 in turn
+    let order = |loot_order| {
+      let params = Params::default().with_loot_order(loot_order);
+      let mut loot = vec![1, 2, 3, 4];
+      params.order_loot(Player(1), &mut loot, &PLAYERS, &mut Rng::with_seed(0));
+      loot
+    };
+
+    assert_eq!(order(LootOrder::AsPlayed), [1, 2, 3, 4]);
+    assert_eq!(order(LootOrder::WinnerFirst), [2, 4, 1, 3]);
+    assert_eq!(order(LootOrder::LoserFirst), [1, 3, 2, 4]);
+    let mut random = order(LootOrder::Random);
+    random.sort_unstable();
+    assert_eq!(random, [1, 2, 3, 4]);
+  }
+
+  #[test]
+  fn suit_tie_break_leaves_no_wars_in_a_single_deck() {
+    let params = Params::default().with_suit_rules(SuitRules {
+      trump: Some(Suit::Hearts),
+      tie_break: true,
+    });
+    for seed in 0..20 {
+      let mut rng = Rng::with_seed(seed);
+      let mut deck = card::shoe(1, 13, 4, 0);
+      rng.shuffle(&mut deck);
+      let players = deck.chunks(26).map(|cards| PlayerDeck::new(cards.to_vec()));
+
+      let mut recorder = Recorder::new(seed);
+      Game::new(params.clone(), rng, players.collect()).play_observed(&mut recorder);
+      assert_eq!(recorder.into_record(&[]).wars, 0, "seed {seed}");
+    }
+  }
+
+  /// The seat of the winner of a battle of `cards`, flipped by players 1, 2, and so on, in a game
+  /// of the ranks 1 to 13 and jokers, or `None` if the battle is a war.
+  fn battle_winner(comparison: Comparison, cards: &[u8]) -> Option<usize> {
+    let mut params = Params::default().with_comparison(comparison);
+    params.deal((1..=13).chain([JOKER, JOKER]));
+    let players = &[Player(0), Player(1), Player(2)][..cards.len()];
+    let (leader, _, _, n_high) = highest(players, cards, params.battle(cards));
+    (n_high == 1).then_some(leader.0)
+  }
+
+  #[test]
+  fn low_cards_beat_high_cards() {
+    let winner = |cards| battle_winner(Comparison::LowBeatsHigh, cards);
+    // A two kills an ace, from either seat
+    assert_eq!(winner(&[1, 13]), Some(0));
+    assert_eq!(winner(&[13, 1]), Some(1));
+    // But then loses to a king
+    assert_eq!(winner(&[1, 12, 13]), Some(1));
+    assert_eq!(winner(&[13, 1, 12]), Some(2));
+    // Without a two, the higher rank wins, and jokers are not killed
+    assert_eq!(winner(&[13, 12]), Some(0));
+    assert_eq!(winner(&[1, JOKER]), Some(1));
+    assert_eq!(winner(&[1, 1]), None);
+
+    assert_eq!(battle_winner(Comparison::Rank, &[1, 13]), Some(1));
+  }
+
+  #[test]
+  fn wild_jokers_tie_the_highest_other_card() {
+    let winner = |cards| battle_winner(Comparison::WildJokers, cards);
+    assert_eq!(winner(&[JOKER, 13]), None);
+    assert_eq!(winner(&[5, JOKER]), None);
+    assert_eq!(winner(&[5, JOKER, 9]), None);
+    assert_eq!(winner(&[9, JOKER, 5]), None);
+    // A joker takes the rank of the highest card only, and otherwise cards compare as usual
+    assert_eq!(winner(&[5, 9, 7]), Some(1));
+    // Jokers flipped only against each other tie
+    assert_eq!(winner(&[JOKER, JOKER]), None);
+    assert_eq!(winner(&[JOKER, JOKER, JOKER]), None);
+
+    assert_eq!(battle_winner(Comparison::Rank, &[JOKER, 13]), Some(0));
+  }
+
+  #[test]
+  fn war_sizes_follow_the_rank_or_the_war() {
+    assert_eq!(WarSize::Fixed.face_down(3, 7, 2), 3);
+    // A tie on fours (rank 3) puts down four cards, and on aces or jokers ten
+    assert_eq!(WarSize::Rank.face_down(3, 3, 1), 4);
+    assert_eq!(WarSize::Rank.face_down(3, 13, 1), 10);
+    assert_eq!(WarSize::Rank.face_down(3, JOKER, 1), 10);
+    let escalating = [1, 2, 3].map(|war| WarSize::Escalating.face_down(3, 7, war));
+    assert_eq!(escalating, [3, 4, 5]);
+  }
+
+  /// Collects the number of cards of every face-down play of a game.
+  struct FaceDowns(Vec<usize>);
+
+  impl Observer for FaceDowns {
+    fn observe(&mut self, event: &Event) {
+      if let Event::FaceDown { cards, .. } = event {
+        self.0.push(cards.len());
       }
     }
   }
+
+  #[test]
+  fn escalating_wars_put_down_one_more_card_each() {
+    // Both players only hold fives, so that every battle is a war until they run out
+    let players = vec![PlayerDeck::new(vec![5; 10]); 2];
+    let params = Params::new(1, 0).with_war_size(WarSize::Escalating);
+    let mut face_downs = FaceDowns(Vec::new());
+    let result = Game::new(params, Rng::with_seed(0), players).play_observed(&mut face_downs);
+    assert!(result == (GameResult::Draw, 1));
+    assert_eq!(face_downs.0, [1, 1, 2, 2, 3, 3]);
+  }
+
+  /// Collects the rank of every war, the number of cards of every face-down play, and the rank of
+  /// every card removed.
+  #[derive(Default, PartialEq, Debug)]
+  struct Wars {
+    ranks: Vec<u8>,
+    face_downs: Vec<usize>,
+    removed: Vec<u8>,
+  }
+
+  impl Observer for Wars {
+    fn observe(&mut self, event: &Event) {
+      match *event {
+        Event::War { rank, .. } => self.ranks.push(rank),
+        Event::FaceDown { cards, .. } => self.face_downs.push(cards.len()),
+        Event::HonorRemoval { card, .. } => self.removed.push(card),
+        _ => {}
+      }
+    }
+  }
+
+  #[test]
+  fn wild_jokers_play_as_the_card_they_copy() {
+    // A joker ties a five, so that the war is on fives, and a four loses to the five by the honor
+    // threshold, whichever of the joker and the five is seated first
+    let params = Params::new(3, 1)
+      .with_comparison(Comparison::WildJokers)
+      .with_war_size(WarSize::Rank);
+    let play = |decks: [Vec<u8>; 3]| {
+      let players = decks.into_iter().map(PlayerDeck::new).collect();
+      let mut wars = Wars::default();
+      let mut game = Game::new(params.clone(), Rng::with_seed(0), players);
+      (game.play_observed(&mut wars), wars)
+    };
+    let (joker, fives, four) = (vec![JOKER], vec![5; 8], vec![4]);
+
+    let ((result, turns), wars) = play([joker.clone(), fives.clone(), four.clone()]);
+    assert!(result == GameResult::Win(Player(1)));
+    assert_eq!(wars.ranks, [5]);
+    assert!(wars.face_downs.contains(&6));
+    assert_eq!(wars.removed, [4]);
+
+    let mirrored = play([fives, joker, four]);
+    assert!(mirrored.0 == (GameResult::Win(Player(0)), turns));
+    assert_eq!(mirrored.1, wars);
+  }
+
+  #[test]
+  fn out_of_cards_policies_decide_short_wars() {
+    // Both players only hold fives, and player 2 holds enough for a war of three cards
+    let play = |player1: Vec<u8>, out_of_cards| {
+      let players = vec![PlayerDeck::new(player1), PlayerDeck::new(vec![5; 5])];
+      let params = Params::new(3, 0).with_out_of_cards(out_of_cards);
+      Game::new(params, Rng::with_seed(0), players).play()
+    };
+    let player2 = (GameResult::Win(Player(1)), 1);
+    let draw = (GameResult::Draw, 1);
+
+    // Player 1 has no face-down card but one to flip, and ties again, after which both are out
+    assert!(play(vec![5, 5], OutOfCards::FlipLast) == draw);
+    assert!(play(vec![5, 5], OutOfCards::ReuseLast) == draw);
+    assert!(play(vec![5, 5], OutOfCards::Lose) == player2);
+
+    // Player 1 has no card left to flip, unless they reuse the five they flipped
+    assert!(play(vec![5], OutOfCards::FlipLast) == player2);
+    assert!(play(vec![5], OutOfCards::ReuseLast) == draw);
+    assert!(play(vec![5], OutOfCards::Lose) == player2);
+  }
+
+  /// Rules that play exactly like `Params`, but are never known to be standard.
+  #[derive(Clone)]
+  struct General(Params);
+
+  impl Ruleset<u8> for General {
+    type Keys = BattleKeys;
+    type Strategy = BuiltinStrategy;
+
+    fn settings(&self) -> Settings {
+      Ruleset::<u8>::settings(&self.0)
+    }
+
+    fn battle(&self, cards: &[u8]) -> BattleKeys {
+      self.0.battle(cards)
+    }
+
+    fn face_down(&self, player: Player, high: u8, war: usize) -> usize {
+      self.0.face_down(player, high, war)
+    }
+
+    fn removes(&self) -> bool {
+      Ruleset::<u8>::removes(&self.0)
+    }
+
+    fn removed<K: Keys<u8>>(
+      &self,
+      battle: Battle<u8, K>,
+      round: Round<u8>,
+    ) -> impl Iterator<Item = usize> {
+      self.0.removed(battle, round)
+    }
+
+    fn buries(&self) -> bool {
+      Ruleset::<u8>::buries(&self.0)
+    }
+
+    fn strategy(&self, player: Player) -> BuiltinStrategy {
+      Ruleset::<u8>::strategy(&self.0, player)
+    }
+
+    fn order_loot(&self, winner: Player, loot: &mut Vec<u8>, owners: &[Player], rng: &mut Rng) {
+      self.0.order_loot(winner, loot, owners, rng)
+    }
+  }
+
+  /// The game of the given seed under `rules`, dealing each of two players half of a shuffled deck
+  /// of four copies of `ranks` ranks.
+  fn game<R: Ruleset<u8>>(rules: R, ranks: u8, seed: u64) -> Game<u8, R> {
+    let mut rng = Rng::with_seed(seed);
+    let mut deck: Vec<u8> = (0..4 * ranks).map(|i| i % ranks + 1).collect();
+    rng.shuffle(&mut deck);
+    let players = deck
+      .chunks(2 * ranks as usize)
+      .map(|cards| PlayerDeck::new(cards.to_vec()));
+    Game::new(rules, rng, players.collect())
+  }
+
+  /// Plays the game of the given seed under `rules`, with a standard deck.
+  fn play<R: Ruleset<u8>>(rules: R, seed: u64) -> (GameResult, u64) {
+    game(rules, 13, seed).play()
+  }
+
+  #[test]
+  fn standard_games_play_like_general_games() {
+    for params in [Params::default(), Params::new(3, 1)] {
+      assert!(Ruleset::<u8>::standard(&params));
+      for seed in 0..100 {
+        let standard = play(params.clone(), seed);
+        assert!(
+          standard == play(General(params.clone()), seed),
+          "seed {seed}"
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn games_that_never_end_are_stopped() {
+    // Small decks whose discards are never shuffled often return to an earlier state
+    let keep = Params::default().with_refill(Refill::Keep);
+    let mut cycles = 0;
+    for seed in 0..100 {
+      let detected = keep.clone().with_cycle_detection(true);
+      let (result, turns) = game(detected, 3, seed).play();
+      if result != GameResult::Cycle {
+        continue;
+      }
+
+      // Without cycle detection, such a game runs until the turn limit
+      cycles += 1;
+      assert!(turns < 1000, "seed {seed}");
+      let limited = keep.clone().with_max_turns(Some(1000));
+      assert!(
+        game(limited, 3, seed).play() == (GameResult::Timeout, 1000),
+        "seed {seed}"
+      );
+    }
+    assert!(cycles > 0);
+  }
 }
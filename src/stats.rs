@@ -0,0 +1,221 @@
+/// The z-score of a two-sided 95% confidence interval of a normal distribution.
+const Z_95: f64 = 1.959_963_984_540_054;
+
+/// Computes the mean of an iterator of f64s.
+pub fn mean(data: impl Iterator<Item = f64>, n: usize) -> f64 {
+  data.sum::<f64>() / n as f64
+}
+
+/// Computes the mean and standard deviation of an iterator of f64s.
+pub fn mean_stddev(data: &[f64]) -> (f64, f64) {
+  let mu = mean(data.iter().copied(), data.len());
+  let variance = mean(data.iter().map(|&x| (x - mu).powi(2)), data.len());
+  (mu, variance.sqrt())
+}
+
+/// The least-squares slope of some points, and their correlation.
+pub struct LinearFit {
+  pub slope: f64,
+  /// The correlation coefficient of the points, from -1 to 1
+  pub correlation: f64,
+}
+
+impl LinearFit {
+  /// Fits a line to the points `(x, y)`. The slope and correlation are NaN if every x is the same.
+  pub fn new(x: &[f64], y: &[f64]) -> Self {
+    let (mean_x, stddev_x) = mean_stddev(x);
+    let (mean_y, stddev_y) = mean_stddev(y);
+    let covariance = mean(
+      x.iter().zip(y).map(|(x, y)| (x - mean_x) * (y - mean_y)),
+      x.len(),
+    );
+
+    let slope = covariance / (stddev_x * stddev_x);
+    Self {
+      slope,
+      correlation: covariance / (stddev_x * stddev_y),
+    }
+  }
+}
+
+/// An estimate of a quantity from a sample, with its standard error and 95% confidence interval.
+#[derive(Clone, Copy)]
+pub struct Estimate {
+  pub value: f64,
+  pub standard_error: f64,
+  pub lower: f64,
+  pub upper: f64,
+}
+
+impl Estimate {
+  /// Estimates a mean from `n` samples with the given mean and standard deviation,
+  /// using the normal approximation.
+  pub fn mean(mean: f64, stddev: f64, n: usize) -> Self {
+    let standard_error = stddev / (n as f64).sqrt();
+
+    Self {
+      value: mean,
+      standard_error,
+      lower: mean - Z_95 * standard_error,
+      upper: mean + Z_95 * standard_error,
+    }
+  }
+
+  /// Estimates a proportion from its observed value in `n` trials, using the Wilson score interval,
+  /// which stays within [0, 1] and behaves well for proportions near 0 or 1.
+  pub fn proportion(p: f64, n: usize) -> Self {
+    // Nothing is known of a proportion without trials, and the bounds cannot be clamped to NaN
+    if n == 0 {
+      return Self {
+        value: f64::NAN,
+        standard_error: f64::NAN,
+        lower: f64::NAN,
+        upper: f64::NAN,
+      };
+    }
+
+    let n = n as f64;
+    let z2 = Z_95 * Z_95;
+
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let half_width = Z_95 / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+
+    // The bounds are clamped, since rounding may take them just past p or outside [0, 1] at p = 0 or 1
+    Self {
+      value: p,
+      standard_error: (p * (1.0 - p) / n).sqrt(),
+      lower: (center - half_width).clamp(0.0, p),
+      upper: (center + half_width).clamp(p, 1.0),
+    }
+  }
+
+  /// Half the width of the confidence interval.
+  pub fn half_width(&self) -> f64 {
+    (self.upper - self.lower) / 2.0
+  }
+}
+
+/// The distribution of the lengths of a batch of games, as the number of games of each length.
+#[derive(Clone, Default)]
+pub struct Histogram {
+  /// The number of games of each number of turns
+  counts: Vec<usize>,
+  n_games: usize,
+}
+
+impl Histogram {
+  pub fn new(lengths: impl IntoIterator<Item = u64>) -> Self {
+    let mut histogram = Self::default();
+    for length in lengths {
+      let length = length as usize;
+      if length >= histogram.counts.len() {
+        histogram.counts.resize(length + 1, 0);
+      }
+
+      histogram.counts[length] += 1;
+      histogram.n_games += 1;
+    }
+    histogram
+  }
+
+  /// The smallest length such that at least a fraction `q` of the games are no longer, or 0 if there
+  /// are no games.
+  pub fn quantile(&self, q: f64) -> u64 {
+    let target = ((q * self.n_games as f64).ceil() as usize).max(1);
+
+    let mut seen = 0;
+    for (length, &count) in self.counts.iter().enumerate() {
+      seen += count;
+      if seen >= target {
+        return length as u64;
+      }
+    }
+    0
+  }
+
+  pub fn median(&self) -> u64 {
+    self.quantile(0.5)
+  }
+
+  pub fn max(&self) -> u64 {
+    self.counts.len().saturating_sub(1) as u64
+  }
+
+  /// The fraction of games per turn in each bin of `bin_width` turns, up to (but excluding) `max`
+  /// turns, so that the bins of every width are on the same scale. The last bin ends at `max`, and
+  /// is only as wide as the turns it covers.
+  pub fn density(&self, bin_width: u64, max: u64) -> Vec<f64> {
+    let (bin_width, max) = (bin_width.max(1) as usize, max as usize);
+    let n_bins = max.div_ceil(bin_width);
+    let len = self.counts.len();
+
+    (0..n_bins)
+      .map(|bin| {
+        let start = bin * bin_width;
+        let end = ((bin + 1) * bin_width).min(max);
+        let count: usize = self.counts[start.min(len)..end.min(len)].iter().sum();
+        count as f64 / (self.n_games.max(1) * (end - start)) as f64
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn proportion_bounds_contain_the_value_within_0_and_1() {
+    for n in [1, 10, 1000, 1_000_000] {
+      for p in [0.0, 0.5, 1.0] {
+        let estimate = Estimate::proportion(p, n);
+        assert!(0.0 <= estimate.lower && estimate.lower <= p);
+        assert!(p <= estimate.upper && estimate.upper <= 1.0);
+      }
+    }
+
+    assert_eq!(Estimate::proportion(0.0, 100).lower, 0.0);
+    assert_eq!(Estimate::proportion(1.0, 100).upper, 1.0);
+  }
+
+  #[test]
+  fn proportion_of_no_trials_is_unknown() {
+    let estimate = Estimate::proportion(f64::NAN, 0);
+    assert!(estimate.value.is_nan() && estimate.lower.is_nan() && estimate.upper.is_nan());
+  }
+
+  #[test]
+  fn histogram_quantiles_are_game_lengths() {
+    let histogram = Histogram::new([4, 1, 3, 2]);
+    // Half of the games are no longer than 2 turns
+    assert_eq!(histogram.median(), 2);
+    assert_eq!(histogram.quantile(0.0), 1);
+    assert_eq!(histogram.quantile(1.0), 4);
+    assert_eq!(histogram.max(), 4);
+    assert_eq!(Histogram::default().median(), 0);
+  }
+
+  #[test]
+  fn histogram_density_is_normalized() {
+    let histogram = Histogram::new([4, 1, 3, 2, 2, 7]);
+    let area = |bin_width: u64, max: u64| -> f64 {
+      let density = histogram.density(bin_width, max);
+      let widths = (0..max)
+        .step_by(bin_width as usize)
+        .map(|start| (max - start).min(bin_width));
+      density.iter().zip(widths).map(|(d, w)| d * w as f64).sum()
+    };
+
+    for bin_width in [1, 2, 3, 8, 10] {
+      assert!((area(bin_width, 8) - 1.0).abs() < 1e-12, "{bin_width}");
+    }
+    // The game of 7 turns is past the last bin
+    assert!((area(2, 7) - 5.0 / 6.0).abs() < 1e-12);
+
+    // The last bin covers only 2 turns, so the game of 7 turns is not spread over 3
+    assert_eq!(
+      histogram.density(3, 8),
+      [3.0 / 18.0, 2.0 / 18.0, 1.0 / 12.0]
+    );
+  }
+}
@@ -0,0 +1,154 @@
+use crate::card::{Ranked, SuitRules};
+use crate::sim::Player;
+use clap::ValueEnum;
+use fastrand::Rng;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+
+/// How a player holding a hand of cards chooses which one to play in a battle (see
+/// `Settings::hand`). Every player flips at the same time, so a strategy only knows what the whole
+/// table has seen: the cards each player won in earlier rounds, which they hold until they play them.
+pub trait Strategy<C: Ranked> {
+  /// The index in `hand` (never empty) of the card `player` plays, where `known` are the cards each
+  /// player is known to hold, in seat order.
+  fn choose(&self, player: Player, hand: &[C], known: &[Vec<C>], rng: &mut Rng) -> usize;
+}
+
+/// The built-in strategies for playing from a hand.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlayStrategy {
+  /// A card at random, which is the same as playing the top card of the deck
+  #[default]
+  Random,
+  /// The highest card
+  Highest,
+  /// The lowest card
+  Lowest,
+  /// The lowest card that beats every card the other players are known to hold, or else the lowest
+  /// card. A player plays at random while nothing is known
+  Counter,
+}
+
+/// A built-in strategy, comparing cards under the suit rules of the game.
+#[derive(Clone, Copy)]
+pub struct BuiltinStrategy {
+  pub strategy: PlayStrategy,
+  pub rules: SuitRules,
+}
+
+impl<C: Ranked> Strategy<C> for BuiltinStrategy {
+  fn choose(&self, player: Player, hand: &[C], known: &[Vec<C>], rng: &mut Rng) -> usize {
+    let rules = self.rules;
+    let key = |&i: &usize| hand[i].key(rules);
+    let indices = 0..hand.len();
+
+    match self.strategy {
+      PlayStrategy::Random => rng.usize(indices),
+      PlayStrategy::Highest => indices.max_by_key(key).unwrap(),
+      PlayStrategy::Lowest => indices.min_by_key(key).unwrap(),
+      PlayStrategy::Counter => {
+        let others = known
+          .iter()
+          .enumerate()
+          .filter(|&(other, _)| other != player.0);
+        let high = others
+          .flat_map(|(_, cards)| cards)
+          .map(|card| card.key(rules))
+          .max();
+        match high {
+          None => rng.usize(indices),
+          Some(high) => {
+            let winning = indices.clone().filter(|i| key(i) > high).min_by_key(key);
+            winning.unwrap_or_else(|| indices.min_by_key(key).unwrap())
+          }
+        }
+      }
+    }
+  }
+}
+
+/// How a player arranges the cards they won in a round before adding them to their discard. This
+/// only matters if discards are not shuffled (`Refill::Keep`), when cards are drawn again in the
+/// order they were won.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LootStrategy {
+  /// The order of the rules (see `LootOrder`)
+  #[default]
+  Rules,
+  /// The highest card first, so that it is drawn first
+  HighFirst,
+  /// The lowest card first
+  LowFirst,
+  /// The highest card, then the lowest, then the second highest, and so on
+  Interleave,
+  /// A random order
+  Random,
+}
+
+impl LootStrategy {
+  /// Arranges `loot` in the chosen order, comparing cards under the suit rules `rules`.
+  pub fn arrange<C: Ranked>(self, loot: &mut [C], rules: SuitRules, rng: &mut Rng) {
+    match self {
+      Self::Rules => {}
+      Self::HighFirst => loot.sort_by_key(|card| Reverse(card.key(rules))),
+      Self::LowFirst => loot.sort_by_key(|card| card.key(rules)),
+      Self::Interleave => {
+        loot.sort_by_key(|card| Reverse(card.key(rules)));
+        let mut sorted: VecDeque<_> = loot.iter().copied().collect();
+        for (i, card) in loot.iter_mut().enumerate() {
+          let next = if i % 2 == 0 {
+            sorted.pop_front()
+          } else {
+            sorted.pop_back()
+          };
+          *card = next.unwrap();
+        }
+      }
+      Self::Random => rng.shuffle(loot),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn counter_beats_the_known_cards_of_the_others() {
+    let counter = BuiltinStrategy {
+      strategy: PlayStrategy::Counter,
+      rules: SuitRules::default(),
+    };
+    let hand: [u8; 3] = [3, 10, 8];
+    let known = [vec![12], vec![5, 7]];
+    let mut rng = Rng::with_seed(0);
+    let mut choose = |player| hand[counter.choose(Player(player), &hand, &known, &mut rng)];
+
+    // Player 1 plays the lowest card above player 2's 7, whatever they hold themselves
+    assert_eq!(choose(0), 8);
+    // Player 2 has no card above player 1's 12, so plays the lowest
+    assert_eq!(choose(1), 3);
+  }
+
+  #[test]
+  fn loot_is_arranged_in_the_chosen_order() {
+    let mut rng = Rng::with_seed(0);
+    let arrange = |strategy: LootStrategy, rng: &mut Rng| {
+      let mut loot: Vec<u8> = vec![4, 9, 2, 7, 5];
+      strategy.arrange(&mut loot, SuitRules::default(), rng);
+      loot
+    };
+
+    assert_eq!(arrange(LootStrategy::Rules, &mut rng), [4, 9, 2, 7, 5]);
+    assert_eq!(arrange(LootStrategy::HighFirst, &mut rng), [9, 7, 5, 4, 2]);
+    assert_eq!(arrange(LootStrategy::LowFirst, &mut rng), [2, 4, 5, 7, 9]);
+    assert_eq!(arrange(LootStrategy::Interleave, &mut rng), [9, 2, 7, 4, 5]);
+
+    let mut random = arrange(LootStrategy::Random, &mut rng);
+    random.sort_unstable();
+    assert_eq!(random, [2, 4, 5, 7, 9]);
+  }
+}
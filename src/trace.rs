@@ -0,0 +1,71 @@
+use crate::card::Ranked;
+use crate::sim::{Event, Observer};
+use std::io::Write;
+
+/// An observer that writes every event of a game as a line of JSON (JSON Lines).
+pub struct JsonLines<W: Write> {
+  writer: W,
+}
+
+impl<W: Write> JsonLines<W> {
+  pub fn new(writer: W) -> Self {
+    Self { writer }
+  }
+}
+
+impl<W: Write, C: Ranked> Observer<C> for JsonLines<W> {
+  fn observe(&mut self, event: &Event<C>) {
+    serde_json::to_writer(&mut self.writer, event).unwrap();
+    self.writer.write_all(b"\n").unwrap();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::card;
+  use crate::sim::{Game, Params, PlayerDeck};
+  use fastrand::Rng;
+  use serde_json::{json, Value};
+
+  #[test]
+  fn every_event_is_a_line_of_json() {
+    let hands = [
+      card::from_ranks(&[13, 1, 5, 5]),
+      card::from_ranks(&[12, 2, 5, 3]),
+    ];
+    let mut game = Game::new(
+      Params::new(1, 0),
+      Rng::with_seed(1),
+      hands.map(PlayerDeck::new).to_vec(),
+    );
+    let mut trace = JsonLines::new(Vec::new());
+    let (result, turns) = game.play_observed(&mut trace);
+
+    let trace = String::from_utf8(trace.writer).unwrap();
+    let events: Vec<Value> = trace
+      .lines()
+      .map(|line| serde_json::from_str(line).unwrap())
+      .collect();
+
+    assert_eq!(events[0], json!({ "event": "round", "turn": 1 }));
+    let rounds = events.iter().filter(|event| event["event"] == "round");
+    assert_eq!(rounds.count() as u64, turns);
+    assert_eq!(
+      events.last().unwrap(),
+      &json!({ "event": "game_end", "result": result.to_string(), "turns": turns })
+    );
+
+    // Cards are written by name, and players by seat
+    let flip = events
+      .iter()
+      .find(|event| event["event"] == "flip")
+      .unwrap();
+    assert_eq!(flip["players"], json!(["player1", "player2"]));
+    assert!(flip["cards"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .all(Value::is_string));
+  }
+}